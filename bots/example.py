@@ -0,0 +1,78 @@
+#!/usr/bin/env python3
+# a small bot for tetris-rs, run it with:
+#   cargo run --features serde -- --bot "python3 bots/example.py"
+#
+# it's sent the game as one line of JSON for every piece and answers with the moves to
+# make (see src/external.rs), anything printed to stderr shows up if it crashes
+#
+# this one just moves each piece over the lowest column and drops it
+
+import json
+import sys
+
+
+def column_heights(rows):
+    heights = []
+    for x in range(len(rows[0])):
+        filled = [y for y, row in enumerate(rows) if row[x] != "."]
+        heights.append(len(rows) - filled[0] if filled else 0)
+    return heights
+
+
+# the cells each piece covers in its 4x4 box as (x, y), the same as Piece::get_base_coords
+CELLS = {
+    ("I", "Up"): [(0, 0), (1, 0), (2, 0), (3, 0)],
+    ("I", "Right"): [(2, 0), (2, 1), (2, 2), (2, 3)],
+    ("O", "Up"): [(1, 0), (2, 0), (1, 1), (2, 1)],
+    ("T", "Up"): [(1, 0), (0, 1), (1, 1), (2, 1)],
+    ("T", "Right"): [(1, 0), (2, 1), (1, 1), (1, 2)],
+    ("T", "Down"): [(1, 1), (0, 0), (1, 0), (2, 0)],
+    ("T", "Left"): [(1, 0), (0, 1), (1, 1), (1, 2)],
+    ("L", "Up"): [(0, 1), (1, 1), (2, 1), (2, 0)],
+    ("L", "Right"): [(1, 0), (1, 1), (1, 2), (2, 2)],
+    ("L", "Down"): [(0, 0), (1, 0), (2, 0), (0, 1)],
+    ("L", "Left"): [(0, 0), (1, 0), (1, 1), (1, 2)],
+    ("J", "Up"): [(0, 1), (1, 1), (2, 1), (0, 0)],
+    ("J", "Right"): [(1, 0), (1, 1), (1, 2), (2, 0)],
+    ("J", "Down"): [(0, 0), (1, 0), (2, 0), (2, 1)],
+    ("J", "Left"): [(1, 0), (1, 1), (1, 2), (0, 2)],
+    ("S", "Up"): [(1, 0), (2, 0), (0, 1), (1, 1)],
+    ("S", "Right"): [(1, 0), (1, 1), (2, 1), (2, 2)],
+    ("Z", "Up"): [(0, 0), (1, 0), (1, 1), (2, 1)],
+    ("Z", "Right"): [(2, 0), (1, 1), (2, 1), (1, 2)],
+}
+
+
+def piece_cells(piece):
+    tetromino, orientation = piece["tetromino"], piece["orientation"]
+    # I, S and Z look the same upside down, and O the same every way round
+    if tetromino == "O":
+        orientation = "Up"
+    elif tetromino in "ISZ":
+        orientation = {"Down": "Up", "Left": "Right"}.get(orientation, orientation)
+    return [(piece["x"] + x, piece["y"] + y) for x, y in CELLS[tetromino, orientation]]
+
+
+def choose_moves(state):
+    piece = state["piece"]
+    cells = piece_cells(piece)
+    # the falling piece is drawn into the board, so its cells are taken back out first
+    rows = [list(row) for row in state["board"]["rows"]]
+    for x, y in cells:
+        rows[y][x] = "."
+
+    heights = column_heights(rows)
+    left = min(x for x, _ in cells)
+    right = max(x for x, _ in cells)
+    # the piece's leftmost cell goes over the lowest column it fits over
+    target = min(range(len(heights) - (right - left)), key=lambda x: heights[x])
+    offset = target - left
+    moves = ["Right"] * offset if offset > 0 else ["Left"] * -offset
+    return moves + ["Drop"]
+
+
+for line in sys.stdin:
+    state = json.loads(line)
+    moves = choose_moves(state)
+    print(f"{state['piece']['tetromino']}: {moves}", file=sys.stderr)
+    print(json.dumps({"moves": moves}), flush=True)
@@ -0,0 +1,323 @@
+use std::path::PathBuf;
+
+use crate::constants::*;
+use crate::game::Randomizer;
+use crate::mode::Mode;
+use crate::opener;
+
+// command line options
+//   --seed <n>        seed for the piece chooser (random if not given)
+//   --randomizer <name>  how the pieces are picked, `random` (each one on its own) or `bag`
+//                     (shuffled bags of all seven), random if not given unless the opener
+//                     bot or trainer is used
+//   --record <file>   write a replay of each game to this file
+//   --replay <file>   play back a recorded replay instead of playing
+//   --save <file>     where to save the game when S is pressed (and load it from the menu)
+//   --resume <file>   continue a saved game straight away
+//   --fumen <data>    start on the board from the first page of a fumen
+//   --export-fumen <file>  write every placement of each game to this file as a fumen
+//   --board <file>    start on a board written in the text notation (see notation.rs), at
+//                     least --height rows tall
+//   --width <n>       number of columns on the board (10 if not given)
+//   --height <n>      number of visible rows on the board (20 if not given)
+//   --mode <name>     endless (the default), sprint, ultra, marathon, dig, survival or zen
+//   --time-limit <s>  seconds ultra mode lasts (120 if not given)
+//   --lines <n>       lines to clear in marathon mode (150 if not given, the level goes
+//                     up every 10 lines up to a tenth of this)
+//                     or garbage rows to dig through in dig mode (18 if not given)
+//   --messiness <x>   chance from 0 to 1 that each garbage row's hole moves (1 if not given)
+//   --players <a>,<b> who plays each side in versus mode, `human` or `bot` (human,bot if
+//                     not given)
+//   --attack-table <file>  garbage sent for each kind of clear in versus mode (see attack.rs)
+//   --host [port]     wait for someone to join a versus match over the network (on port
+//                     7878 if not given), the host's seed, randomizer and board size are used
+//   --join <address>  join a versus match someone is hosting, like 192.168.1.5:7878
+//   --puzzle <file>   play a puzzle (see puzzle.rs) instead of one of the modes
+//   --player <name>   the bot that plays, `random` (the default), `heuristic`, `pc` (goes
+//                     for perfect clears when it can see one, see pc.rs), `opener` (starts
+//                     with whichever opener fits, see opener.rs, or `opener:<name>` for
+//                     one of them), or `bot:<command>` / `tbp:<command>` like --bot and
+//                     --tbp below
+//                     (press D while it plays to see what it's thinking, for the bots
+//                     that say)
+//   --think-time <s>  seconds the bot gets to think about each piece before it's hard
+//                     dropped (1 if not given)
+//   --tbp <command>   let a bot speaking the Tetris Bot Protocol play instead of the random
+//                     one, run as `command` (see tbp.rs, needs the serde feature)
+//   --bot <command>   let a bot written in any language play, run as `command` and sent the
+//                     game as JSON (see external.rs, needs the serde feature)
+//   --tournament <n>  play the bots against each other on `n` seeds instead of playing,
+//                     in score races and versus games, and write a report (see tournament.rs)
+//   --entrant <name>  a bot in the tournament, named like for --player (can be given more
+//                     than once, every built in bot plays if none are)
+//   --report <path>   where the tournament report goes, as <path>.csv and <path>.md
+//                     (tournament if not given), the ratings carry on from the CSV there
+//   --solve-pc <n>    print every perfect clear in `n` lines from the starting board instead
+//                     of playing, using the queue (or all of a puzzle's pieces)
+//   --manual          play with the keyboard instead of letting the bot play (holding left
+//                     or right shifts the piece all the way over, and finesse faults are
+//                     counted, see finesse.rs)
+//   --opener <name>   when playing by hand, show where each piece of an opener goes: `tki`,
+//                     `mko`, `dt-cannon`, `pco` or `any` for whichever fits the first pieces
+//   --tui             run in the terminal instead of opening a window
+pub struct Args {
+    pub seed: Option<u64>,
+    pub randomizer: Randomizer,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub save: PathBuf,
+    pub resume: Option<PathBuf>,
+    pub fumen: Option<String>,
+    pub export_fumen: Option<PathBuf>,
+    pub board: Option<PathBuf>,
+    pub width: usize,
+    pub height: usize,
+    pub mode: Mode,
+    pub puzzle: Option<PathBuf>,
+    // whether each side in versus mode is played with the keyboard
+    pub humans: [bool; 2],
+    pub attack_table: Option<PathBuf>,
+    pub host: Option<u16>,
+    pub join: Option<String>,
+    pub player: String,
+    pub think_time: f64,
+    pub tbp: Option<String>,
+    pub bot: Option<String>,
+    // seeds each pair of bots plays on, when running a tournament
+    pub tournament: Option<usize>,
+    pub entrants: Vec<String>,
+    pub report: PathBuf,
+    // lines to find perfect clears in from the starting board
+    pub solve_pc: Option<usize>,
+    pub manual: bool,
+    // the opener shown while playing by hand, `any` for whichever fits
+    pub opener: Option<String>,
+    pub tui: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Args, String> {
+        let mut args = Args {
+            seed: None,
+            randomizer: Randomizer::Random,
+            record: None,
+            replay: None,
+            save: PathBuf::from(SAVE_FILE),
+            resume: None,
+            fumen: None,
+            export_fumen: None,
+            board: None,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            mode: Mode::Endless,
+            puzzle: None,
+            humans: [true, false],
+            attack_table: None,
+            host: None,
+            join: None,
+            player: "random".to_string(),
+            think_time: THINK_TIME,
+            tbp: None,
+            bot: None,
+            tournament: None,
+            entrants: Vec::new(),
+            report: PathBuf::from(REPORT_FILE),
+            solve_pc: None,
+            manual: false,
+            opener: None,
+            tui: false,
+        };
+
+        // mode settings are applied once the mode is known, so they can come in any order
+        let mut time_limit = None;
+        let mut lines = None;
+        let mut messiness = None;
+        // openers are built from whole bags, so the opener bot and trainer get bags by default
+        let mut randomizer = None;
+
+        let mut iter = std::env::args().skip(1).peekable();
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| format!("missing value for `{}`", arg))
+            };
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = value()?;
+                    args.seed = Some(
+                        seed.parse()
+                            .map_err(|_| format!("invalid seed `{}`", seed))?,
+                    );
+                }
+                "--randomizer" => {
+                    let name = value()?;
+                    randomizer = Some(
+                        Randomizer::from_name(&name)
+                            .ok_or_else(|| format!("unknown randomizer `{}`", name))?,
+                    );
+                }
+                "--record" => args.record = Some(PathBuf::from(value()?)),
+                "--replay" => args.replay = Some(PathBuf::from(value()?)),
+                "--save" => args.save = PathBuf::from(value()?),
+                "--resume" => args.resume = Some(PathBuf::from(value()?)),
+                "--fumen" => args.fumen = Some(value()?),
+                "--export-fumen" => args.export_fumen = Some(PathBuf::from(value()?)),
+                "--board" => args.board = Some(PathBuf::from(value()?)),
+                "--width" => args.width = parse_size(&value()?)?,
+                "--height" => args.height = parse_size(&value()?)?,
+                "--mode" => {
+                    let name = value()?;
+                    args.mode =
+                        Mode::from_name(&name).ok_or_else(|| format!("unknown mode `{}`", name))?;
+                }
+                "--time-limit" => {
+                    let limit = value()?;
+                    time_limit = Some(
+                        limit
+                            .parse()
+                            .ok()
+                            .filter(|&limit: &f64| limit > 0.0)
+                            .ok_or_else(|| format!("invalid time limit `{}`", limit))?,
+                    );
+                }
+                "--lines" => {
+                    let count = value()?;
+                    lines = Some(
+                        count
+                            .parse()
+                            .ok()
+                            .filter(|&count: &usize| count > 0)
+                            .ok_or_else(|| format!("invalid line count `{}`", count))?,
+                    );
+                }
+                "--messiness" => {
+                    let chance = value()?;
+                    messiness = Some(
+                        chance
+                            .parse()
+                            .ok()
+                            .filter(|chance: &f64| (0.0..=1.0).contains(chance))
+                            .ok_or_else(|| format!("invalid messiness `{}`", chance))?,
+                    );
+                }
+                "--players" => {
+                    let players = value()?;
+                    let sides: Vec<&str> = players.split(',').collect();
+                    args.humans = match sides[..] {
+                        [a, b] => [is_human(a)?, is_human(b)?],
+                        _ => return Err(format!("expected two players in `{}`", players)),
+                    };
+                }
+                "--attack-table" => args.attack_table = Some(PathBuf::from(value()?)),
+                "--host" => {
+                    // the port is optional, so only take the next argument if it's one
+                    let port = iter.next_if(|port| !port.starts_with("--"));
+                    args.host = Some(match port {
+                        Some(port) => port
+                            .parse()
+                            .map_err(|_| format!("invalid port `{}`", port))?,
+                        None => DEFAULT_PORT,
+                    });
+                }
+                "--join" => args.join = Some(value()?),
+                "--player" => args.player = value()?,
+                "--think-time" => {
+                    let time = value()?;
+                    args.think_time = time
+                        .parse()
+                        .ok()
+                        .filter(|&time: &f64| time > 0.0)
+                        .ok_or_else(|| format!("invalid think time `{}`", time))?;
+                }
+                "--tbp" => args.tbp = Some(value()?),
+                "--bot" => args.bot = Some(value()?),
+                "--puzzle" => args.puzzle = Some(PathBuf::from(value()?)),
+                "--tournament" => {
+                    let games = value()?;
+                    args.tournament = Some(
+                        games
+                            .parse()
+                            .ok()
+                            .filter(|&games| games > 0)
+                            .ok_or_else(|| format!("invalid number of games `{}`", games))?,
+                    );
+                }
+                "--entrant" => args.entrants.push(value()?),
+                "--report" => args.report = PathBuf::from(value()?),
+                "--solve-pc" => {
+                    let count = value()?;
+                    args.solve_pc = Some(
+                        count
+                            .parse()
+                            .ok()
+                            .filter(|&count: &usize| count > 0)
+                            .ok_or_else(|| format!("invalid line count `{}`", count))?,
+                    );
+                }
+                "--manual" => args.manual = true,
+                "--opener" => {
+                    let name = value()?;
+                    if name != "any" && opener::find(&name).is_none() {
+                        return Err(format!("unknown opener `{}`", name));
+                    }
+                    args.opener = Some(name);
+                }
+                "--tui" => args.tui = true,
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+
+        match &mut args.mode {
+            Mode::Ultra { time_limit: limit } => *limit = time_limit.unwrap_or(*limit),
+            Mode::Marathon { lines: goal } => *goal = lines.unwrap_or(*goal),
+            Mode::Dig {
+                lines: goal,
+                messiness: chance,
+            } => {
+                *goal = lines.unwrap_or(*goal);
+                *chance = messiness.unwrap_or(*chance);
+            }
+            _ => (),
+        }
+        let uses_opener =
+            args.opener.is_some() || args.player == "opener" || args.player.starts_with("opener:");
+        args.randomizer = match randomizer {
+            Some(randomizer) => randomizer,
+            None if uses_opener => Randomizer::Bag,
+            None => Randomizer::Random,
+        };
+        if args.puzzle.is_some() {
+            args.mode = Mode::Puzzle;
+        }
+        if args.host.is_some() || args.join.is_some() {
+            args.mode = Mode::Versus;
+        }
+        if args.tui && args.replay.is_some() {
+            return Err("replays need a window, they can't be watched with --tui".to_string());
+        }
+        if args.tui && args.mode == Mode::Versus {
+            return Err("versus mode needs a window, it can't be played with --tui".to_string());
+        }
+
+        Ok(args)
+    }
+}
+
+fn is_human(player: &str) -> Result<bool, String> {
+    match player {
+        "human" => Ok(true),
+        "bot" => Ok(false),
+        _ => Err(format!(
+            "unknown player `{}`, expected human or bot",
+            player
+        )),
+    }
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(size) if size >= MIN_SIZE => Ok(size),
+        Ok(_) => Err(format!("board size must be at least {}", MIN_SIZE)),
+        Err(_) => Err(format!("invalid board size `{}`", value)),
+    }
+}
@@ -0,0 +1,202 @@
+// how much garbage clears send to the opponent in versus mode
+//
+// attack table files have one `key value` line per setting, anything left out keeps
+// its default:
+//   single 0
+//   double 1
+//   triple 2
+//   tetris 4
+//   t-spin-single 2
+//   t-spin-double 4
+//   t-spin-triple 6
+//   back-to-back 1
+//   perfect-clear 10
+//   combos 0 1 1 2 2 3 3 4 4 4 5
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::Lock;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AttackTable {
+    // rows sent for singles, doubles, triples and tetrises
+    pub clears: [usize; 4],
+    // rows sent for T-spin singles, doubles and triples
+    pub t_spins: [usize; 3],
+    // extra rows for a tetris or T-spin clear when the last clear was one too
+    pub back_to_back: usize,
+    // extra rows for a clear that leaves the board empty
+    pub perfect_clear: usize,
+    // extra rows for each clear in a row, by how many clears came just before it
+    // (the last one keeps going for longer combos)
+    pub combos: Vec<usize>,
+}
+
+impl Default for AttackTable {
+    fn default() -> AttackTable {
+        AttackTable {
+            clears: [0, 1, 2, 4],
+            t_spins: [2, 4, 6],
+            back_to_back: 1,
+            perfect_clear: 10,
+            combos: vec![0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        }
+    }
+}
+
+impl AttackTable {
+    pub fn load(path: &Path) -> io::Result<AttackTable> {
+        let text = fs::read_to_string(path)?;
+        let mut table = AttackTable::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data(i + 1, "expected `key value`"))?;
+            let bad_value = || invalid_data(i + 1, &format!("bad value for `{}`", key));
+            let rows = || value.parse::<usize>().map_err(|_| bad_value());
+            match key {
+                "single" => table.clears[0] = rows()?,
+                "double" => table.clears[1] = rows()?,
+                "triple" => table.clears[2] = rows()?,
+                "tetris" => table.clears[3] = rows()?,
+                "t-spin-single" => table.t_spins[0] = rows()?,
+                "t-spin-double" => table.t_spins[1] = rows()?,
+                "t-spin-triple" => table.t_spins[2] = rows()?,
+                "back-to-back" => table.back_to_back = rows()?,
+                "perfect-clear" => table.perfect_clear = rows()?,
+                "combos" => {
+                    table.combos = value
+                        .split_whitespace()
+                        .map(|rows| rows.parse().ok())
+                        .collect::<Option<_>>()
+                        .ok_or_else(bad_value)?
+                }
+                _ => return Err(invalid_data(i + 1, &format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(table)
+    }
+}
+
+// keeps track of the combo and back to back between locks
+#[derive(Clone, Default)]
+pub struct Attacker {
+    // clears in a row so far
+    pub combo: usize,
+    // whether the last clear was a tetris or T-spin
+    pub back_to_back: bool,
+    // rows sent over the whole game, before any were cancelled
+    pub sent: usize,
+}
+
+impl Attacker {
+    // how many rows a lock sends
+    pub fn attack(&mut self, table: &AttackTable, lock: Lock) -> usize {
+        if lock.lines == 0 {
+            self.combo = 0;
+            return 0;
+        }
+        let difficult = lock.lines == 4 || lock.t_spin;
+        let mut rows = if lock.t_spin {
+            table.t_spins[lock.lines - 1]
+        } else {
+            table.clears[lock.lines - 1]
+        };
+        if difficult && self.back_to_back {
+            rows += table.back_to_back;
+        }
+        self.back_to_back = difficult;
+        rows += table
+            .combos
+            .get(self.combo)
+            .or(table.combos.last())
+            .copied()
+            .unwrap_or(0);
+        self.combo += 1;
+        if lock.perfect_clear {
+            rows += table.perfect_clear;
+        }
+        self.sent += rows;
+        rows
+    }
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("attack table line {}: {}", line, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(lines: usize, t_spin: bool, perfect_clear: bool) -> Lock {
+        Lock {
+            lines,
+            t_spin,
+            perfect_clear,
+        }
+    }
+
+    #[test]
+    fn default_table_lookups() {
+        let table = AttackTable::default();
+        let mut attacker = Attacker::default();
+        let sent: Vec<usize> = [
+            lock(1, false, false), // single, no combo yet
+            lock(2, false, false), // double + combo 1
+            lock(4, false, false), // tetris + combo 1
+            lock(2, true, false),  // t-spin double + back to back + combo 2
+            lock(0, false, false), // breaks the combo
+            lock(3, true, false),  // t-spin triple + back to back
+            lock(1, false, false), // single + combo 1, ends back to back
+            lock(4, false, true),  // tetris + combo 1 + perfect clear
+        ]
+        .into_iter()
+        .map(|lock| attacker.attack(&table, lock))
+        .collect();
+        assert_eq!(sent, [0, 2, 5, 7, 0, 7, 1, 15]);
+        assert_eq!(attacker.sent, sent.iter().sum::<usize>());
+        assert!(attacker.back_to_back);
+    }
+
+    #[test]
+    fn long_combos_keep_the_last_bonus() {
+        let table = AttackTable::default();
+        let mut attacker = Attacker::default();
+        for _ in 0..table.combos.len() {
+            attacker.attack(&table, lock(1, false, false));
+        }
+        assert_eq!(attacker.attack(&table, lock(1, false, false)), 5);
+    }
+
+    #[test]
+    fn loads_a_partial_table() {
+        let path = std::env::temp_dir().join(format!("tetris-rs-{}.attack", std::process::id()));
+        fs::write(&path, "double 2\n\ncombos 0 2\n").unwrap();
+        let table = AttackTable::load(&path);
+        fs::write(&path, "tetris four\n").unwrap();
+        let bad = AttackTable::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            table.unwrap(),
+            AttackTable {
+                clears: [0, 2, 2, 4],
+                combos: vec![0, 2],
+                ..AttackTable::default()
+            }
+        );
+        assert_eq!(
+            bad.unwrap_err().to_string(),
+            "attack table line 1: bad value for `tetris`"
+        );
+    }
+}
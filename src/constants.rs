@@ -1,7 +1,76 @@
-pub const WIDTH: usize = 10;
-pub const HEIGHT: usize = 24;
+// board size when none is given on the command line (not counting the hidden rows)
+pub const DEFAULT_WIDTH: usize = 10;
+pub const DEFAULT_HEIGHT: usize = 20;
+// rows above the visible field that pieces spawn in, blocks can still be stacked up into them
+pub const HIDDEN_ROWS: usize = 4;
+// every piece has to fit on the board in any orientation
+pub const MIN_SIZE: usize = 4;
 
 pub const PLACEMENT_DELAY: f64 = 0.5;
+// seconds between each gravity tick
+pub const DROP_TIME: f64 = 1.0;
+// seconds a left or right key is held before the piece shifts all the way over (DAS)
+pub const DAS_DELAY: f64 = 0.15;
+// how many pieces are shown in the next queue
+pub const NUM_NEXT_PIECES: usize = 3;
+
+// where the game is saved to and loaded from when no path is given
+pub const SAVE_FILE: &str = "tetris.sav";
+
+// where personal bests are kept
+pub const RECORDS_FILE: &str = "tetris.records";
+
+// where tournament reports go (with .csv and .md added) when no path is given
+pub const REPORT_FILE: &str = "tournament";
+
+// lines to clear in sprint mode, with a split taken every `SPLIT_LINES`
+pub const SPRINT_LINES: usize = 40;
+pub const SPLIT_LINES: usize = 10;
+// seconds to score in for ultra mode when no time limit is given
+pub const ULTRA_TIME_LIMIT: f64 = 120.0;
+// lines to clear in marathon mode when none are given, the level goes up every `LINES_PER_LEVEL`
+pub const MARATHON_LINES: usize = 150;
+pub const LINES_PER_LEVEL: usize = 10;
+// garbage rows to dig through in dig mode when none are given, and how many are on
+// the board at once (more come up as they're cleared)
+pub const DIG_LINES: usize = 18;
+pub const DIG_ROWS: usize = 10;
+// how likely each garbage row's hole is to move away from the one below it
+pub const DIG_MESSINESS: f64 = 1.0;
+// seconds until the first garbage row comes up in survival mode, each one after comes
+// a bit quicker (the wait is multiplied by `SURVIVAL_SPEEDUP`) down to `SURVIVAL_MIN_INTERVAL`
+pub const SURVIVAL_INTERVAL: f64 = 4.0;
+pub const SURVIVAL_SPEEDUP: f64 = 0.97;
+pub const SURVIVAL_MIN_INTERVAL: f64 = 0.5;
+// seconds garbage sent in versus mode waits before it can come up, giving the other
+// side a chance to cancel it by clearing lines
+pub const GARBAGE_DELAY: f64 = 1.0;
+// in network play, how many steps go by between board hashes being checked, and how
+// many seconds between measuring the round trip
+pub const HASH_STEPS: u64 = 60;
+pub const PING_INTERVAL: f64 = 1.0;
+// how many steps after the one being played a move goes in, giving it time to get to the
+// other side before that step is due
+pub const NET_INPUT_DELAY: u64 = 6;
+// the port hosts listen on when none is given
+pub const DEFAULT_PORT: u16 = 7878;
+
+// seconds a bot gets to think about each piece before it's hard dropped for it
+pub const THINK_TIME: f64 = 1.0;
+// how many of the placements a bot looked at are drawn over the board when debugging it
+pub const SHOWN_CANDIDATES: usize = 5;
+// how many lines the perfect clear bot tries to fit a perfect clear in
+pub const PC_LINES: usize = 4;
+
+// tournament games are played on a made up clock going up by `FRAME_TIME` seconds a frame,
+// and stop after `TOURNAMENT_PIECES` pieces each
+// network matches are played in steps of `FRAME_TIME` seconds too
+pub const FRAME_TIME: f64 = 1.0 / 60.0;
+pub const TOURNAMENT_PIECES: usize = 200;
+// ratings everyone starts on, and how far an Elo rating moves after each game
+pub const START_RATING: f64 = 1500.0;
+pub const START_DEVIATION: f64 = 350.0;
+pub const ELO_K: f64 = 32.0;
 
 // the maximum number of tetrominos that can be placed on the board
 pub const TETROMINO_LIMIT: usize = 400;
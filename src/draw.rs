@@ -1,9 +1,12 @@
 use macroquad::prelude::*;
 
 use crate::constants::*;
-use crate::game::{Piece, Tetromino};
+use crate::game::{Board, Piece, Tetromino};
+use crate::player::Thoughts;
 
-pub fn draw_tetris_grid(width: usize, height: usize) {
+// boards are drawn with their left edge at `left`, which is `MARGIN_LEFT` unless
+// there's more than one on screen
+pub fn draw_tetris_grid(width: usize, height: usize, left: f32) {
     // inner rectangle fill
     // every alternating line is a different color
     let mut darkenning_factor;
@@ -30,7 +33,7 @@ pub fn draw_tetris_grid(width: usize, height: usize) {
                 )
             };
             draw_rectangle(
-                x as f32 * SQUARE_SIZE + MARGIN_LEFT,
+                x as f32 * SQUARE_SIZE + left,
                 y as f32 * SQUARE_SIZE + MARGIN_TOP,
                 SQUARE_SIZE,
                 SQUARE_SIZE,
@@ -41,7 +44,7 @@ pub fn draw_tetris_grid(width: usize, height: usize) {
 
     // draw bounding box
     draw_rectangle_lines(
-        MARGIN_LEFT,
+        left,
         MARGIN_TOP,
         width as f32 * SQUARE_SIZE,
         height as f32 * SQUARE_SIZE,
@@ -50,19 +53,19 @@ pub fn draw_tetris_grid(width: usize, height: usize) {
     );
 }
 
-pub fn draw_menu() {
+pub fn draw_menu(width: usize, height: usize) {
     // shadow
     draw_text(
         "Tetris",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 5.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 5.0,
         100.0,
         BLACK,
     );
     draw_text(
         "Tetris",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 40.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 40.0,
+        height as f32 * SQUARE_SIZE / 2.0,
         100.0,
         YELLOW,
     );
@@ -70,33 +73,49 @@ pub fn draw_menu() {
     // shadow
     draw_text(
         "Press space to start",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 50.0 + 5.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 50.0 + 5.0,
         25.0,
         BLACK,
     );
     draw_text(
         "Press space to start",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 50.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 50.0,
+        25.0,
+        WHITE,
+    );
+
+    // shadow
+    draw_text(
+        "Press L to load saved game",
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 80.0 + 5.0,
+        25.0,
+        BLACK,
+    );
+    draw_text(
+        "Press L to load saved game",
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 80.0,
         25.0,
         WHITE,
     );
 }
 
-pub fn draw_game_over(score: u32) {
+pub fn draw_game_over(title: &str, score: u32, width: usize, height: usize) {
     // shadow
     draw_text(
-        "Game over!",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 120.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 5.0,
+        title,
+        width as f32 * SQUARE_SIZE / 2.0 - 120.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 5.0,
         100.0,
         BLACK,
     );
     draw_text(
-        "Game over!",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 120.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0,
+        title,
+        width as f32 * SQUARE_SIZE / 2.0 - 120.0,
+        height as f32 * SQUARE_SIZE / 2.0,
         100.0,
         RED,
     );
@@ -104,15 +123,15 @@ pub fn draw_game_over(score: u32) {
     // score shadow
     draw_text(
         &format!("Score {}", score),
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 20.0 + 4.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 50.0 + 4.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0 + 4.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 50.0 + 4.0,
         50.0,
         BLACK,
     );
     draw_text(
         &format!("Score {}", score),
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 20.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 50.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 20.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 50.0,
         50.0,
         WHITE,
     );
@@ -120,21 +139,21 @@ pub fn draw_game_over(score: u32) {
     // shadow
     draw_text(
         "Press space to restart",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 100.0 + 5.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 100.0 + 5.0,
         25.0,
         BLACK,
     );
     draw_text(
         "Press space to restart",
-        WIDTH as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
-        HEIGHT as f32 * SQUARE_SIZE / 2.0 + 100.0,
+        width as f32 * SQUARE_SIZE / 2.0 - 40.0 + 5.0,
+        height as f32 * SQUARE_SIZE / 2.0 + 100.0,
         25.0,
         WHITE,
     );
 }
 
-pub fn draw_tetro(tetro: &Tetromino, x: usize, y: usize) {
+pub fn draw_tetro(tetro: &Tetromino, x: usize, y: usize, left: f32) {
     let color;
     if let Tetromino::E = tetro {
         return;
@@ -143,7 +162,7 @@ pub fn draw_tetro(tetro: &Tetromino, x: usize, y: usize) {
     }
     // outer rectangle
     draw_rectangle(
-        x as f32 * SQUARE_SIZE + MARGIN_LEFT,
+        x as f32 * SQUARE_SIZE + left,
         y as f32 * SQUARE_SIZE + MARGIN_TOP,
         SQUARE_SIZE,
         SQUARE_SIZE,
@@ -153,7 +172,7 @@ pub fn draw_tetro(tetro: &Tetromino, x: usize, y: usize) {
     let inner_offset = (SQUARE_SIZE - inner_size) / 2.0;
     // inner rectangle
     draw_rectangle(
-        x as f32 * SQUARE_SIZE + inner_offset + MARGIN_LEFT,
+        x as f32 * SQUARE_SIZE + inner_offset + left,
         y as f32 * SQUARE_SIZE + inner_offset + MARGIN_TOP,
         inner_size,
         inner_size,
@@ -161,9 +180,22 @@ pub fn draw_tetro(tetro: &Tetromino, x: usize, y: usize) {
     );
 }
 
-pub fn draw_score(score: u32) {
+pub fn draw_board(board: &Board, left: f32) {
+    for (y, row) in board.visible_rows().iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            draw_tetro(cell, x, y, left);
+        }
+    }
+}
+
+// the side panel sits just right of the board
+fn side_panel_middle(width: usize, left: f32) -> f32 {
+    left - MARGIN_LEFT + width as f32 * SQUARE_SIZE + SIDE_PANEL_WIDTH / 2.0
+}
+
+pub fn draw_score(score: u32, width: usize, left: f32) {
     // display the score
-    let side_panel_middle = WIDTH as f32 * SQUARE_SIZE + SIDE_PANEL_WIDTH / 2.0;
+    let side_panel_middle = side_panel_middle(width, left);
     let side_panel_margin_left = side_panel_middle - SIDE_PANEL_WIDTH / 5.0;
     // draw bounding box for the whole score
     draw_rectangle(
@@ -205,9 +237,9 @@ pub fn draw_score(score: u32) {
     );
 }
 
-pub fn draw_next_pieces(next_pieces: &[Tetromino]) {
+pub fn draw_next_pieces(next_pieces: &[Tetromino], width: usize, left: f32) {
     // display the next pieces
-    let side_panel_middle = WIDTH as f32 * SQUARE_SIZE + SIDE_PANEL_WIDTH / 2.0;
+    let side_panel_middle = side_panel_middle(width, left);
     let side_panel_margin_left = side_panel_middle - SIDE_PANEL_WIDTH / 5.0;
 
     // draw bounding box for the next pieces
@@ -245,7 +277,7 @@ pub fn draw_next_pieces(next_pieces: &[Tetromino]) {
 
             // outer rectangle
             draw_rectangle(
-                side_panel_middle + 10.0 + (x - 3) as f32 * SQUARE_SIZE * 0.75,
+                side_panel_middle + 10.0 + x as f32 * SQUARE_SIZE * 0.75,
                 MARGIN_TOP * 5.0
                     + y as f32 * SQUARE_SIZE * 0.75
                     + SQUARE_SIZE * 2.0 * 0.75
@@ -259,7 +291,7 @@ pub fn draw_next_pieces(next_pieces: &[Tetromino]) {
             let inner_size = SQUARE_SIZE * 0.4 * (0.75 / 0.5);
             let inner_offset = (SQUARE_SIZE * 0.75 - inner_size) / 2.0;
             draw_rectangle(
-                side_panel_middle + 10.0 + (x - 3) as f32 * SQUARE_SIZE * 0.75 + inner_offset,
+                side_panel_middle + 10.0 + x as f32 * SQUARE_SIZE * 0.75 + inner_offset,
                 MARGIN_TOP * 5.0
                     + y as f32 * SQUARE_SIZE * 0.75
                     + SQUARE_SIZE * 2.0 * 0.75
@@ -272,3 +304,275 @@ pub fn draw_next_pieces(next_pieces: &[Tetromino]) {
         }
     }
 }
+
+// `problem` is anything wrong with the replay, shown above the board
+pub fn draw_replay_status(
+    time: f64,
+    duration: f64,
+    speed: f64,
+    paused: bool,
+    problem: Option<&str>,
+    height: usize,
+) {
+    let status = if paused { "Paused" } else { "Playing" };
+    // shadow
+    draw_text(
+        &format!("{} {:.1}/{:.1}s x{}", status, time, duration, speed),
+        MARGIN_LEFT + 1.5,
+        (height + 2) as f32 * SQUARE_SIZE + 1.5,
+        25.0,
+        BLACK,
+    );
+    draw_text(
+        &format!("{} {:.1}/{:.1}s x{}", status, time, duration, speed),
+        MARGIN_LEFT,
+        (height + 2) as f32 * SQUARE_SIZE,
+        25.0,
+        WHITE,
+    );
+    draw_text(
+        "Space pause  N step  Left/Right seek  Up/Down speed",
+        MARGIN_LEFT,
+        (height + 2) as f32 * SQUARE_SIZE + 20.0,
+        18.0,
+        GRAY,
+    );
+    if let Some(problem) = problem {
+        draw_text(problem, MARGIN_LEFT, MARGIN_TOP - 8.0, 18.0, RED);
+    }
+}
+
+pub fn draw_hold(held: Option<Tetromino>, width: usize, left: f32) {
+    // display the held piece under the next pieces
+    let side_panel_middle = side_panel_middle(width, left);
+    let side_panel_margin_left = side_panel_middle - SIDE_PANEL_WIDTH / 5.0;
+
+    // draw bounding box for the held piece
+    draw_rectangle(
+        side_panel_margin_left,
+        MARGIN_TOP * 14.0,
+        SIDE_PANEL_WIDTH,
+        SQUARE_SIZE * 4.0,
+        Color::from_rgba(70, 70, 70, 255),
+    );
+    // "Hold" shadow
+    draw_text(
+        "Hold",
+        side_panel_middle + 10.0 + 1.5,
+        MARGIN_TOP * 14.0 + 1.7 + SQUARE_SIZE,
+        35.0,
+        BLACK,
+    );
+    draw_text(
+        "Hold",
+        side_panel_middle + 10.0,
+        MARGIN_TOP * 14.0 + SQUARE_SIZE,
+        35.0,
+        GOLD,
+    );
+
+    let Some(held) = held else {
+        return;
+    };
+    let color = held.get_color();
+    for &(x, y) in &Piece::new(held).get_coords() {
+        // outer rectangle
+        draw_rectangle(
+            side_panel_middle + 10.0 + x as f32 * SQUARE_SIZE * 0.75,
+            MARGIN_TOP * 14.0 + y as f32 * SQUARE_SIZE * 0.75 + SQUARE_SIZE * 2.0 * 0.75,
+            SQUARE_SIZE * 0.75,
+            SQUARE_SIZE * 0.75,
+            BLACK,
+        );
+
+        // inner rectangle
+        let inner_size = SQUARE_SIZE * 0.4 * (0.75 / 0.5);
+        let inner_offset = (SQUARE_SIZE * 0.75 - inner_size) / 2.0;
+        draw_rectangle(
+            side_panel_middle + 10.0 + x as f32 * SQUARE_SIZE * 0.75 + inner_offset,
+            MARGIN_TOP * 14.0
+                + y as f32 * SQUARE_SIZE * 0.75
+                + SQUARE_SIZE * 2.0 * 0.75
+                + inner_offset,
+            inner_size,
+            inner_size,
+            color,
+        );
+    }
+}
+
+pub fn draw_stats(stats: &[String], width: usize, left: f32) {
+    // display the time, lines and so on under the held piece
+    let side_panel_middle = side_panel_middle(width, left);
+    let side_panel_margin_left = side_panel_middle - SIDE_PANEL_WIDTH / 5.0;
+
+    // draw bounding box for the stats
+    draw_rectangle(
+        side_panel_margin_left,
+        MARGIN_TOP * 19.0,
+        SIDE_PANEL_WIDTH,
+        SQUARE_SIZE * 0.8 * stats.len() as f32 + SQUARE_SIZE * 0.4,
+        Color::from_rgba(70, 70, 70, 255),
+    );
+    for (i, stat) in stats.iter().enumerate() {
+        let y = MARGIN_TOP * 19.0 + SQUARE_SIZE * 0.8 * (i + 1) as f32;
+        // shadow
+        draw_text(
+            stat,
+            side_panel_margin_left + 10.0 + 1.0,
+            y + 1.0,
+            22.0,
+            BLACK,
+        );
+        draw_text(stat, side_panel_margin_left + 10.0, y, 22.0, WHITE);
+    }
+}
+
+pub fn draw_results(results: &[String], width: usize, height: usize) {
+    // listed under the game over screen's restart prompt
+    for (i, result) in results.iter().enumerate() {
+        let y = height as f32 * SQUARE_SIZE / 2.0 + 130.0 + i as f32 * 22.0;
+        // shadow
+        draw_text(
+            result,
+            width as f32 * SQUARE_SIZE / 2.0 - 40.0 + 2.0,
+            y + 2.0,
+            22.0,
+            BLACK,
+        );
+        draw_text(
+            result,
+            width as f32 * SQUARE_SIZE / 2.0 - 40.0,
+            y,
+            22.0,
+            WHITE,
+        );
+    }
+}
+
+// garbage waiting to come up in versus mode, as a bar left of the board
+// the rows at the bottom are ready to come up and the ones above are still on their way
+pub fn draw_garbage_meter(waiting: usize, ready: usize, height: usize, left: f32) {
+    let bottom = MARGIN_TOP + height as f32 * SQUARE_SIZE;
+    let x = left - SQUARE_SIZE * 0.5;
+    let bar_width = SQUARE_SIZE * 0.3;
+    let waiting = waiting.min(height) as f32 * SQUARE_SIZE;
+    let ready = ready.min(height) as f32 * SQUARE_SIZE;
+    draw_rectangle(x, bottom - waiting, bar_width, waiting, ORANGE);
+    draw_rectangle(x, bottom - ready, bar_width, ready, RED);
+}
+
+// shown over both boards once a versus match is over, `window_width` is in pixels
+pub fn draw_versus_over(title: &str, prompt: &str, window_width: f32, height: usize) {
+    let x = window_width / 2.0 - 160.0;
+    let y = height as f32 * SQUARE_SIZE / 2.0;
+    // shadow
+    draw_text(title, x + 5.0, y + 5.0, 80.0, BLACK);
+    draw_text(title, x, y, 80.0, RED);
+
+    // shadow
+    draw_text(prompt, x + 40.0 + 3.0, y + 50.0 + 3.0, 25.0, BLACK);
+    draw_text(prompt, x + 40.0, y + 50.0, 25.0, WHITE);
+}
+
+// outlines where a piece should go, in its colour
+pub fn draw_hint(piece: &Piece, left: f32) {
+    let color = piece.tetromino.get_color();
+    for (x, y) in piece.get_coords() {
+        let y = y - HIDDEN_ROWS as isize;
+        if y < 0 {
+            continue;
+        }
+        let (x, y) = (
+            x as f32 * SQUARE_SIZE + left,
+            y as f32 * SQUARE_SIZE + MARGIN_TOP,
+        );
+        draw_rectangle(x, y, SQUARE_SIZE, SQUARE_SIZE, Color { a: 0.2, ..color });
+        draw_rectangle_lines(x, y, SQUARE_SIZE, SQUARE_SIZE, 3.0, color);
+    }
+}
+
+// what a bot considered for the current piece, drawn over the board: its best few
+// placements see-through with their scores (the chosen one outlined), the path the chosen
+// one takes to get there, and how it scored feature by feature in the top left corner
+pub fn draw_thoughts(thoughts: &Thoughts, left: f32) {
+    // where a cell's top left corner is on screen, or None if it's above the visible field
+    let corner = |(x, y): (isize, isize)| {
+        let y = y - HIDDEN_ROWS as isize;
+        (y >= 0).then_some((
+            x as f32 * SQUARE_SIZE + left,
+            y as f32 * SQUARE_SIZE + MARGIN_TOP,
+        ))
+    };
+
+    // the worst first, so the best ends up on top
+    for (i, (piece, score)) in thoughts.candidates.iter().enumerate().rev() {
+        let color = piece.tetromino.get_color();
+        let alpha = if i == 0 { 0.6 } else { 0.25 };
+        for (x, y) in piece.get_coords().into_iter().filter_map(corner) {
+            draw_rectangle(x, y, SQUARE_SIZE, SQUARE_SIZE, Color { a: alpha, ..color });
+            if i == 0 {
+                draw_rectangle_lines(x, y, SQUARE_SIZE, SQUARE_SIZE, 2.0, WHITE);
+            }
+        }
+        // the score goes on the piece's top left cell
+        if let Some((x, y)) = piece
+            .get_coords()
+            .into_iter()
+            .min_by_key(|&(x, y)| (y, x))
+            .and_then(corner)
+        {
+            draw_text(
+                &format!("{:.1}", score),
+                x + 2.0,
+                y + SQUARE_SIZE * 0.6,
+                16.0,
+                WHITE,
+            );
+        }
+    }
+
+    // a line through the middle of the piece at each step
+    let middles: Vec<(f32, f32)> = thoughts
+        .path
+        .iter()
+        .filter_map(|piece| {
+            let corners: Vec<_> = piece.get_coords().into_iter().filter_map(corner).collect();
+            (corners.len() == 4).then(|| {
+                let x = corners.iter().map(|(x, _)| x).sum::<f32>() / 4.0;
+                let y = corners.iter().map(|(_, y)| y).sum::<f32>() / 4.0;
+                (x + SQUARE_SIZE / 2.0, y + SQUARE_SIZE / 2.0)
+            })
+        })
+        .collect();
+    for pair in middles.windows(2) {
+        draw_line(pair[0].0, pair[0].1, pair[1].0, pair[1].1, 3.0, WHITE);
+    }
+    for &(x, y) in &middles {
+        draw_circle(x, y, 4.0, WHITE);
+    }
+
+    let total: f64 = thoughts.breakdown.iter().map(|(_, _, term)| term).sum();
+    let mut lines: Vec<String> = thoughts
+        .breakdown
+        .iter()
+        .map(|(name, value, term)| format!("{} {} = {:.2}", name, value, term))
+        .collect();
+    lines.push(format!("total {:.2}", total));
+    draw_rectangle(
+        left + 4.0,
+        MARGIN_TOP + 4.0,
+        SQUARE_SIZE * 6.0,
+        lines.len() as f32 * 18.0 + 8.0,
+        Color::from_rgba(0, 0, 0, 160),
+    );
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            line,
+            left + 10.0,
+            MARGIN_TOP + 20.0 + i as f32 * 18.0,
+            18.0,
+            WHITE,
+        );
+    }
+}
@@ -0,0 +1,317 @@
+// bots written in any language, run as a subprocess and talked to with one JSON message
+// per line, only compiled with the `serde` feature
+//
+// for every piece the bot is sent the game (the JSON shapes are in serialization.rs):
+//   {"board": {"rows": [...], "score": 0}, "piece": {...}, "next": ["T", "L", ...],
+//    "held": null, "can_hold": true}
+// with the falling piece drawn into the board and the next pieces in the order they'll come,
+// and answers with the moves to make:
+//   {"moves": ["Rotate", "Left", "Left", "Drop"]}
+// a bot that doesn't answer in `BOT_TIMEOUT` seconds, quits or answers with something that
+// isn't that has crashed, and is started again for the next piece (that piece is hard
+// dropped), up to `BOT_RESTARTS` times before it's given up on
+// what the bot writes to stderr is kept, and why it crashed is shown under the stats along
+// with the last line of it
+// the command is split into the program and its arguments like a shell would, so arguments
+// with spaces in can be quoted, like `python3 "my bots/bot.py"`
+//
+// bots/example.py is a small bot to start from
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::*;
+use crate::player::Player;
+
+// seconds a bot gets to answer before it's given up on
+const BOT_TIMEOUT: f64 = 5.0;
+// how many times a bot that crashes is started again
+const BOT_RESTARTS: usize = 3;
+// how many of the last lines of a bot's stderr are kept
+const STDERR_LINES: usize = 10;
+// seconds a stopped bot's stderr gets to finish being read
+const STDERR_WAIT: f64 = 0.5;
+
+// a bot's process, with its stdout and stderr read by background threads so a bot that
+// hangs can be given up on
+pub struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    incoming: Receiver<io::Result<String>>,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    // disconnects when the stderr reader is done
+    stderr_done: Receiver<()>,
+}
+
+impl BotProcess {
+    // runs `command`, split into the program and its arguments
+    pub fn spawn(command: &str) -> io::Result<BotProcess> {
+        let words = split_command(command)?;
+        let (program, args) = words
+            .split_first()
+            .ok_or_else(|| invalid_input("empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        let kept = Arc::new(Mutex::new(VecDeque::new()));
+        let lines = Arc::clone(&kept);
+        let (done, stderr_done) = mpsc::channel();
+        thread::spawn(move || {
+            let _done = done;
+            for line in stderr.lines().map_while(Result::ok) {
+                let mut lines = lines.lock().unwrap();
+                if lines.len() == STDERR_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        });
+
+        Ok(BotProcess {
+            child,
+            stdin,
+            incoming,
+            stderr: kept,
+            stderr_done,
+        })
+    }
+
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    // waits up to `BOT_TIMEOUT` for the next line
+    pub fn receive_line(&mut self) -> io::Result<String> {
+        match self
+            .incoming
+            .recv_timeout(Duration::from_secs_f64(BOT_TIMEOUT))
+        {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the bot took too long to answer",
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = match self.child.wait() {
+                    Ok(status) => format!(" ({})", status),
+                    _ => String::new(),
+                };
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("the bot quit{}", status),
+                ))
+            }
+        }
+    }
+
+    // kills the bot and returns the last few lines it wrote to stderr
+    pub fn stop(&mut self) -> Vec<String> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        // whatever it wrote just before it went is only all there once the reader is done,
+        // which it might never be if something the bot started still has its stderr open
+        let _ = self
+            .stderr_done
+            .recv_timeout(Duration::from_secs_f64(STDERR_WAIT));
+        self.stderr.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+// splits a command into words on whitespace, except inside single or double quotes, with a
+// backslash taking the next character as it is (apart from inside single quotes)
+fn split_command(command: &str) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    // the word being read, if one has started (`""` starts an empty one)
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| invalid_input("bot command ends in a backslash"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(invalid_input("unclosed quote in bot command"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Serialize)]
+struct State<'a> {
+    board: &'a Board,
+    piece: &'a Piece,
+    next: Vec<Tetromino>,
+    held: Option<Tetromino>,
+    can_hold: bool,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    moves: Vec<Move>,
+}
+
+struct Bot {
+    process: Option<BotProcess>,
+    restarts: usize,
+    // why it last crashed and the last thing it wrote to stderr before then
+    error: Option<String>,
+    last_words: Option<String>,
+}
+
+pub struct ExternalPlayer {
+    pub command: String,
+    bot: RefCell<Bot>,
+}
+
+impl ExternalPlayer {
+    // starts the bot straight away, so one that can't be run is found out before playing
+    pub fn launch(command: &str) -> io::Result<ExternalPlayer> {
+        Ok(ExternalPlayer {
+            command: command.to_string(),
+            bot: RefCell::new(Bot {
+                process: Some(BotProcess::spawn(command)?),
+                restarts: 0,
+                error: None,
+                last_words: None,
+            }),
+        })
+    }
+}
+
+fn ask(process: &mut BotProcess, game: &Game) -> io::Result<Vec<Move>> {
+    let state = State {
+        board: &game.board,
+        piece: &game.piece,
+        next: game
+            .piece_chooser
+            .next_pieces
+            .iter()
+            .rev()
+            .copied()
+            .collect(),
+        held: game.held,
+        can_hold: game.can_hold,
+    };
+    process.send_line(&serde_json::to_string(&state)?)?;
+    let answer: Answer = serde_json::from_str(&process.receive_line()?)?;
+    Ok(answer.moves)
+}
+
+impl Player for ExternalPlayer {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        let mut bot = self.bot.borrow_mut();
+        if bot.process.is_none() && bot.restarts < BOT_RESTARTS {
+            bot.restarts += 1;
+            match BotProcess::spawn(&self.command) {
+                Ok(process) => bot.process = Some(process),
+                Err(err) => {
+                    bot.error = Some(format!("Couldn't restart: {}", err));
+                    bot.restarts = BOT_RESTARTS;
+                }
+            }
+        }
+        let Some(process) = &mut bot.process else {
+            // a bot that's gone for good just hard drops everything
+            return vec![Move::Drop];
+        };
+
+        match ask(process, game) {
+            Ok(moves) => moves,
+            Err(err) => {
+                let last_words = process.stop().pop();
+                bot.error = Some(format!("Crashed: {}", err));
+                bot.last_words = last_words;
+                bot.process = None;
+                vec![Move::Drop]
+            }
+        }
+    }
+
+    fn status(&self) -> Vec<String> {
+        let bot = self.bot.borrow();
+        let mut status = Vec::new();
+        if bot.restarts > 0 {
+            status.push(format!("Restarts {}/{}", bot.restarts, BOT_RESTARTS));
+        }
+        if bot.process.is_none() && bot.restarts == BOT_RESTARTS {
+            status.push("Gave up on the bot".to_string());
+        }
+        status.extend(bot.error.clone());
+        status.extend(bot.last_words.clone());
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_split_like_a_shell_would() {
+        let split = |command| split_command(command).unwrap();
+        assert_eq!(
+            split("python3 bots/example.py"),
+            ["python3", "bots/example.py"]
+        );
+        assert_eq!(split("  a   b\tc "), ["a", "b", "c"]);
+        assert_eq!(
+            split(r#"python3 "my bots/bot.py" --name 'Jo "JJ" Smith'"#),
+            ["python3", "my bots/bot.py", "--name", r#"Jo "JJ" Smith"#]
+        );
+        assert_eq!(split(r#"my\ bot '\n' x\"y"#), ["my bot", r"\n", "x\"y"]);
+        assert_eq!(split(r#"bot "" ''"#), ["bot", "", ""]);
+        assert!(split("").is_empty());
+        assert!(split_command("bot 'unclosed").is_err());
+        assert!(split_command(r"bot \").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+// finesse: getting each piece into place with as few key presses as possible, which is a lot
+// of what playing fast comes down to
+//
+// each piece placed by hand is judged against the fewest inputs that would have taken it from
+// where it spawned to somewhere a hard drop puts it where it went, each of these being one:
+//   left or right a column
+//   left or right all the way to the wall (holding the key down, see `DAS_DELAY`)
+//   rotating
+// soft drops, hard drops and holds aren't counted, and pieces a hard drop can't get to where
+// they went (tucked under something or spun in) aren't judged at all
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::game::*;
+
+// an input that moves or turns a piece before it's dropped
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    Left,
+    Right,
+    WallLeft,
+    WallRight,
+    Rotate,
+}
+
+impl Input {
+    // short enough for the side panel
+    fn symbol(self) -> &'static str {
+        match self {
+            Input::Left => "<",
+            Input::Right => ">",
+            Input::WallLeft => "<<",
+            Input::WallRight => ">>",
+            Input::Rotate => "r",
+        }
+    }
+}
+
+// the fewest inputs that take a piece from where it spawns on `board` to where a hard drop
+// leaves it like `piece`, or None if that can't be done (or the piece couldn't spawn)
+// `board` shouldn't have `piece` on it
+pub fn fewest_inputs(board: &Board, piece: &Piece) -> Option<Vec<Input>> {
+    let mut board = board.clone();
+    let start = board.spawn_piece(piece.tetromino)?;
+    let mut target = piece.get_coords();
+    target.sort();
+
+    // moves a piece on `board`, which is otherwise kept without it
+    let mut step = |piece: &Piece, mov: Move| {
+        let mut piece = piece.clone();
+        board.add_piece(&piece);
+        board.move_piece(&mut piece, mov);
+        board.remove_piece(&piece);
+        piece
+    };
+
+    // breadth first, so the first way found is one of the shortest
+    let key = |piece: &Piece| (piece.x, piece.y, piece.orientation as u8);
+    let mut seen = HashSet::from([key(&start)]);
+    let mut queue = VecDeque::from([(start, Vec::new())]);
+    while let Some((piece, inputs)) = queue.pop_front() {
+        let mut dropped = step(&piece, Move::Drop).get_coords();
+        dropped.sort();
+        if dropped == target {
+            return Some(inputs);
+        }
+        for input in [
+            Input::Left,
+            Input::Right,
+            Input::WallLeft,
+            Input::WallRight,
+            Input::Rotate,
+        ] {
+            let moved = match input {
+                Input::Left => step(&piece, Move::Left),
+                Input::Right => step(&piece, Move::Right),
+                Input::Rotate => step(&piece, Move::Rotate),
+                Input::WallLeft | Input::WallRight => {
+                    let mov = if input == Input::WallLeft {
+                        Move::Left
+                    } else {
+                        Move::Right
+                    };
+                    let mut moved = piece.clone();
+                    loop {
+                        let next = step(&moved, mov);
+                        if next == moved {
+                            break moved;
+                        }
+                        moved = next;
+                    }
+                }
+            };
+            if seen.insert(key(&moved)) {
+                let mut inputs = inputs.clone();
+                inputs.push(input);
+                queue.push_back((moved, inputs));
+            }
+        }
+    }
+    None
+}
+
+// inputs made on the piece that's falling, and how the pieces so far have gone
+#[derive(Clone, Default)]
+pub struct Finesse {
+    pub inputs: usize,
+    // pieces judged, the ones that took more inputs than they needed, and how many more
+    pub pieces: usize,
+    pub faults: usize,
+    pub extra: usize,
+    // the inputs the last piece judged needed, if it took more than that
+    pub last_fault: Option<Vec<Input>>,
+}
+
+impl Finesse {
+    // counts a key pressed for the piece that's falling
+    pub fn input(&mut self, mov: Move) {
+        if matches!(mov, Move::Left | Move::Right | Move::Rotate) {
+            self.inputs += 1;
+        }
+    }
+
+    // judges a piece as it locks, `board` being the board without it
+    pub fn lock(&mut self, board: &Board, piece: &Piece) {
+        let inputs = std::mem::take(&mut self.inputs);
+        let Some(needed) = fewest_inputs(board, piece) else {
+            return;
+        };
+        self.pieces += 1;
+        self.last_fault = None;
+        if inputs > needed.len() {
+            self.faults += 1;
+            self.extra += inputs - needed.len();
+            self.last_fault = Some(needed);
+        }
+    }
+
+    // shown while playing, with what the last piece should have been done with if it
+    // was a fault
+    pub fn stats(&self) -> Vec<String> {
+        if self.pieces == 0 {
+            return Vec::new();
+        }
+        let mut stats = vec![format!("Faults {}", self.faults)];
+        if let Some(needed) = &self.last_fault {
+            let inputs: Vec<&str> = needed.iter().map(|input| input.symbol()).collect();
+            stats.push(if inputs.is_empty() {
+                "Just drop".to_string()
+            } else {
+                format!("Do {}", inputs.join(" "))
+            });
+        }
+        stats
+    }
+
+    // for the game over screen
+    pub fn results(&self) -> Vec<String> {
+        if self.pieces == 0 {
+            return Vec::new();
+        }
+        vec![format!(
+            "Finesse faults {} in {} pieces ({:.0}%, {} extra inputs)",
+            self.faults,
+            self.pieces,
+            self.faults as f64 * 100.0 / self.pieces as f64,
+            self.extra
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // where a piece ends up after these moves and a hard drop on an empty board
+    fn dropped(tetromino: Tetromino, moves: &[Move]) -> (Board, Piece) {
+        let mut board = Board::new(10, 20);
+        let mut piece = board.spawn_piece(tetromino).unwrap();
+        board.add_piece(&piece);
+        for &mov in moves.iter().chain(&[Move::Drop]) {
+            board.move_piece(&mut piece, mov);
+        }
+        board.remove_piece(&piece);
+        (board, piece)
+    }
+
+    #[test]
+    fn fewest_inputs_to_common_places() {
+        let fewest = |tetromino, moves: &[Move]| {
+            let (board, piece) = dropped(tetromino, moves);
+            fewest_inputs(&board, &piece).unwrap()
+        };
+        assert_eq!(fewest(Tetromino::O, &[]), []);
+        assert_eq!(fewest(Tetromino::O, &[Move::Right]), [Input::Right]);
+        assert_eq!(fewest(Tetromino::O, &[Move::Left; 8]), [Input::WallLeft]);
+        assert_eq!(fewest(Tetromino::T, &[Move::Right; 8]), [Input::WallRight]);
+        // one short of the wall is quicker from the wall than a column at a time
+        assert_eq!(
+            fewest(Tetromino::O, &[Move::Left; 3]),
+            [Input::WallLeft, Input::Right]
+        );
+        // an I standing up against the right wall has to be turned before it goes there
+        let mut moves = vec![Move::Rotate];
+        moves.extend([Move::Right; 8]);
+        assert_eq!(
+            fewest(Tetromino::I, &moves),
+            [Input::Rotate, Input::WallRight]
+        );
+    }
+
+    #[test]
+    fn tucked_pieces_are_not_judged() {
+        let mut board = Board::new(10, 20);
+        // a roof over the left of the board, with an O slid under it
+        let floor = board.height() - 1;
+        for x in 0..4 {
+            board.grid[floor - 2][x] = Tetromino::G;
+        }
+        let mut piece = Piece::new(Tetromino::O);
+        piece.x = -1;
+        piece.y = floor as isize - 1;
+        assert!(fewest_inputs(&board, &piece).is_none());
+
+        let mut finesse = Finesse::default();
+        finesse.input(Move::Left);
+        finesse.lock(&board, &piece);
+        assert_eq!((finesse.pieces, finesse.inputs), (0, 0));
+    }
+
+    #[test]
+    fn extra_inputs_are_faults() {
+        let (board, piece) = dropped(Tetromino::O, &[Move::Left; 4]);
+        let mut finesse = Finesse::default();
+        for _ in 0..4 {
+            finesse.input(Move::Left);
+        }
+        // soft drops don't count
+        finesse.input(Move::Down);
+        finesse.lock(&board, &piece);
+        assert_eq!((finesse.pieces, finesse.faults, finesse.extra), (1, 1, 3));
+        assert_eq!(finesse.stats(), ["Faults 1", "Do <<"]);
+
+        finesse.input(Move::Left);
+        let (board, piece) = dropped(Tetromino::O, &[Move::Left]);
+        finesse.lock(&board, &piece);
+        assert_eq!((finesse.pieces, finesse.faults), (2, 1));
+        assert_eq!(finesse.stats(), ["Faults 1"]);
+    }
+}
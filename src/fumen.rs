@@ -0,0 +1,650 @@
+// encoding and decoding of fumen (v115) strings, the format used by the
+// tetris community (fumen.zui.jp, harddrop wiki, ...) to share boards
+//
+// a fumen is a list of pages, each with a field, an optional piece and a comment
+// the field is 23 rows high plus a hidden garbage row, so the top row of our board
+// is dropped when exporting
+
+use crate::constants::*;
+use crate::game::*;
+
+const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8; 95] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+const FIELD_WIDTH: usize = 10;
+const FIELD_TOP: usize = 23;
+// the visible rows plus the garbage row underneath
+const FIELD_CELLS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+
+// fumen rotation codes
+const REVERSE: u32 = 0;
+const RIGHT: u32 = 1;
+const SPAWN: u32 = 2;
+const LEFT: u32 = 3;
+
+type Field = [u8; FIELD_CELLS];
+
+pub struct FumenPage {
+    // the field as shown on the page, without the piece
+    pub board: Board,
+    pub piece: Option<Piece>,
+    pub comment: String,
+    // whether the piece gets placed (and lines cleared) before the next page
+    pub lock: bool,
+}
+
+pub fn decode(data: &str) -> Result<Vec<FumenPage>, String> {
+    // accept whole urls as well as the bare data
+    let data = match data.find("v115@") {
+        Some(start) => &data[start + 5..],
+        None => return Err("only v115 fumens are supported".to_string()),
+    };
+    let mut values = Values::decode(data)?;
+
+    let mut pages = Vec::new();
+    let mut prev_field = [0; FIELD_CELLS];
+    let mut prev_comment = String::new();
+    let mut repeat_count = 0;
+    while !values.is_empty() {
+        let mut field = prev_field;
+        if repeat_count > 0 {
+            repeat_count -= 1;
+        } else {
+            let mut changed = true;
+            let mut index = 0;
+            while index < FIELD_CELLS {
+                let value = values.poll(2)?;
+                let diff = value / FIELD_CELLS as u32;
+                let count = value % FIELD_CELLS as u32 + 1;
+                if diff == 8 && count == FIELD_CELLS as u32 {
+                    changed = false;
+                }
+                for _ in 0..count {
+                    if index >= FIELD_CELLS {
+                        return Err("field data runs past the end of the field".to_string());
+                    }
+                    let cell = field[index] as i32 + diff as i32 - 8;
+                    if !(0..=8).contains(&cell) {
+                        return Err("invalid field data".to_string());
+                    }
+                    field[index] = cell as u8;
+                    index += 1;
+                }
+            }
+            if !changed {
+                repeat_count = values.poll(1)?;
+            }
+        }
+
+        let mut action = values.poll(3)?;
+        let piece_type = action % 8;
+        action /= 8;
+        let rotation = action % 4;
+        action /= 4;
+        let location = action % FIELD_CELLS as u32;
+        action /= FIELD_CELLS as u32;
+        let rise = action % 2 == 1;
+        action /= 2;
+        let mirror = action % 2 == 1;
+        action /= 2;
+        // colorize flag, only matters to the quiz feature
+        action /= 2;
+        let has_comment = action % 2 == 1;
+        action /= 2;
+        let lock = action % 2 == 0;
+
+        let comment = if has_comment {
+            let length = values.poll(2)? as usize;
+            let mut escaped = Vec::with_capacity(length);
+            while escaped.len() < length {
+                let mut chunk = values.poll(5)?;
+                for _ in 0..4.min(length - escaped.len()) {
+                    escaped.push(*COMMENT_TABLE.get((chunk % 96) as usize).unwrap_or(&b' '));
+                    chunk /= 96;
+                }
+            }
+            unescape(&String::from_utf8_lossy(&escaped))
+        } else {
+            prev_comment.clone()
+        };
+
+        let cells = match code_to_tetromino(piece_type) {
+            Some(tetromino) => Some((
+                tetromino,
+                piece_cells(tetromino, rotation, location)
+                    .ok_or_else(|| "piece is out of the field".to_string())?,
+            )),
+            None => None,
+        };
+        let board = field_to_board_grid(&field);
+        let piece = match cells {
+            Some((tetromino, cells)) => Some(
+                Piece::from_cells(
+                    tetromino,
+                    cells.map(|(x, y)| flip_rows(x, y, board.height())),
+                )
+                .ok_or_else(|| "piece doesn't fit the board".to_string())?,
+            ),
+            None => None,
+        };
+
+        pages.push(FumenPage {
+            board,
+            piece,
+            comment: comment.clone(),
+            lock,
+        });
+
+        if lock {
+            if let Some((tetromino, cells)) = cells {
+                for (x, y) in cells {
+                    field[cell_index(x, y)] = tetromino_to_code(tetromino) as u8;
+                }
+            }
+            clear_lines(&mut field);
+            if rise {
+                rise_garbage(&mut field);
+            }
+            if mirror {
+                mirror_field(&mut field);
+            }
+        }
+        prev_field = field;
+        prev_comment = comment;
+    }
+
+    Ok(pages)
+}
+
+pub fn encode(pages: &[FumenPage]) -> String {
+    let mut values = Values { data: Vec::new() };
+
+    let mut prev_field = [0; FIELD_CELLS];
+    let mut prev_comment = String::new();
+    // where the repeat counter of the last unchanged field is, so following unchanged fields can bump it
+    let mut last_repeat_index: Option<usize> = None;
+    for page in pages {
+        let mut field = board_to_field(&page.board);
+
+        // run length encode the difference from the previous field
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for index in 0..FIELD_CELLS {
+            let diff = (field[index] as i32 - prev_field[index] as i32 + 8) as u32;
+            match runs.last_mut() {
+                Some((last_diff, count)) if *last_diff == diff => *count += 1,
+                _ => runs.push((diff, 1)),
+            }
+        }
+        let changed = runs != [(8, FIELD_CELLS as u32)];
+        if changed {
+            for (diff, count) in runs {
+                values.push(diff * FIELD_CELLS as u32 + count - 1, 2);
+            }
+            last_repeat_index = None;
+        } else {
+            match last_repeat_index {
+                Some(index) if values.data[index] < 63 => values.data[index] += 1,
+                _ => {
+                    values.push(8 * FIELD_CELLS as u32 + FIELD_CELLS as u32 - 1, 2);
+                    values.push(0, 1);
+                    last_repeat_index = Some(values.data.len() - 1);
+                }
+            }
+        }
+
+        let placement = page.piece.as_ref().and_then(|piece| {
+            let cells = piece
+                .get_coords()
+                .map(|(x, y)| flip_rows(x, y, page.board.height()));
+            locate_piece(piece.tetromino, cells)
+                .map(|(rotation, location)| (piece, cells, rotation, location))
+        });
+        let (piece_type, rotation, location) = match placement {
+            Some((piece, _, rotation, location)) => {
+                (tetromino_to_code(piece.tetromino), rotation, location)
+            }
+            None => (0, REVERSE, 0),
+        };
+        let has_comment = page.comment != prev_comment;
+        let mut action = !page.lock as u32;
+        action = action * 2 + has_comment as u32;
+        // colorize is on for every page like the fumen editor does
+        action = action * 2 + 1;
+        // no mirroring
+        action *= 2;
+        // no rising garbage
+        action *= 2;
+        action = action * FIELD_CELLS as u32 + location;
+        action = action * 4 + rotation;
+        action = action * 8 + piece_type;
+        values.push(action, 3);
+
+        if has_comment {
+            let escaped = escape(&page.comment);
+            let escaped: Vec<u32> = escaped
+                .bytes()
+                .take(4095)
+                .map(|c| COMMENT_TABLE.iter().position(|&t| t == c).unwrap_or(0) as u32)
+                .collect();
+            values.push(escaped.len() as u32, 2);
+            for chunk in escaped.chunks(4) {
+                let mut value = 0;
+                for &c in chunk.iter().rev() {
+                    value = value * 96 + c;
+                }
+                values.push(value, 5);
+            }
+        }
+
+        if page.lock {
+            if let Some((piece, cells, _, _)) = placement {
+                for (x, y) in cells {
+                    field[cell_index(x, y)] = tetromino_to_code(piece.tetromino) as u8;
+                }
+            }
+            clear_lines(&mut field);
+        }
+        prev_field = field;
+        prev_comment = page.comment.clone();
+    }
+
+    let data: String = values
+        .data
+        .iter()
+        .map(|&value| TABLE[value as usize] as char)
+        .collect();
+    format!("v115@{}", data)
+}
+
+// base64-ish digits, multi digit values are stored least significant digit first
+struct Values {
+    data: Vec<u32>,
+}
+
+impl Values {
+    fn decode(data: &str) -> Result<Values, String> {
+        let mut values = Vec::new();
+        for c in data.bytes() {
+            // fumen breaks long strings up with question marks
+            if c == b'?' {
+                continue;
+            }
+            match TABLE.iter().position(|&t| t == c) {
+                Some(value) => values.push(value as u32),
+                None => return Err(format!("invalid character `{}`", c as char)),
+            }
+        }
+        values.reverse();
+        Ok(Values { data: values })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn poll(&mut self, digits: usize) -> Result<u32, String> {
+        let mut value = 0;
+        let mut scale = 1;
+        for _ in 0..digits {
+            let digit = self
+                .data
+                .pop()
+                .ok_or_else(|| "unexpected end of fumen data".to_string())?;
+            value += digit * scale;
+            scale *= 64;
+        }
+        Ok(value)
+    }
+
+    fn push(&mut self, mut value: u32, digits: usize) {
+        for _ in 0..digits {
+            self.data.push(value % 64);
+            value /= 64;
+        }
+    }
+}
+
+fn tetromino_to_code(tetromino: Tetromino) -> u32 {
+    match tetromino {
+        Tetromino::E => 0,
+        Tetromino::I => 1,
+        Tetromino::L => 2,
+        Tetromino::O => 3,
+        Tetromino::Z => 4,
+        Tetromino::T => 5,
+        Tetromino::J => 6,
+        Tetromino::S => 7,
+        Tetromino::G => 8,
+    }
+}
+
+fn code_to_tetromino(code: u32) -> Option<Tetromino> {
+    match code {
+        1 => Some(Tetromino::I),
+        2 => Some(Tetromino::L),
+        3 => Some(Tetromino::O),
+        4 => Some(Tetromino::Z),
+        5 => Some(Tetromino::T),
+        6 => Some(Tetromino::J),
+        7 => Some(Tetromino::S),
+        _ => None,
+    }
+}
+
+// fumen coordinates have x going right and y going up from the bottom row (y = 0),
+// the garbage row sits at y = -1
+fn cell_index(x: isize, y: isize) -> usize {
+    (FIELD_TOP as isize - y - 1) as usize * FIELD_WIDTH + x as usize
+}
+
+// board rows count down from the top, fumen rows count up from the bottom
+// flipping is its own inverse so this converts both ways
+fn flip_rows(x: isize, y: isize, height: usize) -> (isize, isize) {
+    (x, height as isize - 1 - y)
+}
+
+// fumen fields are always 10 wide, so decoded boards get the default size
+fn field_to_board_grid(field: &Field) -> Board {
+    let mut board = Board::new(FIELD_WIDTH, DEFAULT_HEIGHT);
+    for y in 0..FIELD_TOP as isize {
+        let (_, row) = flip_rows(0, y, board.height());
+        if row < 0 {
+            continue;
+        }
+        for x in 0..FIELD_WIDTH {
+            board.grid[row as usize][x] = match field[cell_index(x as isize, y)] {
+                0 => Tetromino::E,
+                code => code_to_tetromino(code as u32).unwrap_or(Tetromino::G),
+            };
+        }
+    }
+    board
+}
+
+fn board_to_field(board: &Board) -> Field {
+    let mut field = [0; FIELD_CELLS];
+    for y in 0..FIELD_TOP as isize {
+        let (_, row) = flip_rows(0, y, board.height());
+        if row < 0 {
+            continue;
+        }
+        // anything past the edge of a wider board is left out
+        for x in 0..FIELD_WIDTH.min(board.width()) {
+            field[cell_index(x as isize, y)] = tetromino_to_code(board.grid[row as usize][x]) as u8;
+        }
+    }
+    field
+}
+
+// srs shapes around the rotation center, in the spawn orientation
+fn shape(tetromino: Tetromino) -> [(isize, isize); 4] {
+    match tetromino {
+        Tetromino::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        Tetromino::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        Tetromino::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Tetromino::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        Tetromino::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        Tetromino::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        Tetromino::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        Tetromino::E | Tetromino::G => [(0, 0); 4],
+    }
+}
+
+fn rotated_shape(tetromino: Tetromino, rotation: u32) -> [(isize, isize); 4] {
+    shape(tetromino).map(|(x, y)| match rotation {
+        SPAWN => (x, y),
+        RIGHT => (y, -x),
+        REVERSE => (-x, -y),
+        _ => (-y, x),
+    })
+}
+
+// fumen stores some pieces by a different cell than their srs rotation center, so that each
+// of them is stored by the same cell whichever way round it is (the O by its top left cell)
+fn center_offset(tetromino: Tetromino, rotation: u32) -> (isize, isize) {
+    match (tetromino, rotation) {
+        (Tetromino::O, LEFT) => (1, -1),
+        (Tetromino::O, REVERSE) => (1, 0),
+        (Tetromino::O, SPAWN) => (0, -1),
+        (Tetromino::I, REVERSE) => (1, 0),
+        (Tetromino::I, LEFT) => (0, -1),
+        (Tetromino::S, SPAWN) => (0, -1),
+        (Tetromino::S, RIGHT) => (-1, 0),
+        (Tetromino::Z, SPAWN) => (0, -1),
+        (Tetromino::Z, LEFT) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+fn piece_cells(tetromino: Tetromino, rotation: u32, location: u32) -> Option<[(isize, isize); 4]> {
+    let (dx, dy) = center_offset(tetromino, rotation);
+    let x = (location as usize % FIELD_WIDTH) as isize + dx;
+    let y = FIELD_TOP as isize - (location as usize / FIELD_WIDTH) as isize - 1 + dy;
+    let cells = rotated_shape(tetromino, rotation).map(|(cx, cy)| (x + cx, y + cy));
+    let in_field = cells.iter().all(|&(x, y)| {
+        (0..FIELD_WIDTH as isize).contains(&x) && (0..FIELD_TOP as isize).contains(&y)
+    });
+    in_field.then_some(cells)
+}
+
+// the inverse of piece_cells, finds the rotation and location that cover these cells
+fn locate_piece(tetromino: Tetromino, cells: [(isize, isize); 4]) -> Option<(u32, u32)> {
+    let mut cells = cells;
+    cells.sort();
+    for rotation in [SPAWN, RIGHT, REVERSE, LEFT] {
+        let mut shape = rotated_shape(tetromino, rotation);
+        shape.sort();
+        let x = cells[0].0 - shape[0].0;
+        let y = cells[0].1 - shape[0].1;
+        let mut placed = shape.map(|(cx, cy)| (x + cx, y + cy));
+        placed.sort();
+        if placed != cells {
+            continue;
+        }
+
+        let (dx, dy) = center_offset(tetromino, rotation);
+        let (x, y) = (x - dx, y - dy);
+        if (0..FIELD_WIDTH as isize).contains(&x) && (0..FIELD_TOP as isize).contains(&y) {
+            return Some((rotation, cell_index(x, y) as u32));
+        }
+    }
+    None
+}
+
+fn clear_lines(field: &mut Field) {
+    // the garbage row never clears
+    let mut rows: Vec<[u8; FIELD_WIDTH]> = (0..FIELD_TOP)
+        .map(|row| {
+            let mut cells = [0; FIELD_WIDTH];
+            cells.copy_from_slice(&field[row * FIELD_WIDTH..(row + 1) * FIELD_WIDTH]);
+            cells
+        })
+        .filter(|cells| cells.contains(&0))
+        .collect();
+    while rows.len() < FIELD_TOP {
+        rows.insert(0, [0; FIELD_WIDTH]);
+    }
+    for (row, cells) in rows.iter().enumerate() {
+        field[row * FIELD_WIDTH..(row + 1) * FIELD_WIDTH].copy_from_slice(cells);
+    }
+}
+
+fn rise_garbage(field: &mut Field) {
+    // everything moves up a row, the top row is lost and the garbage row is emptied
+    field.copy_within(FIELD_WIDTH.., 0);
+    let garbage_row = FIELD_TOP * FIELD_WIDTH;
+    field[garbage_row..].fill(0);
+}
+
+fn mirror_field(field: &mut Field) {
+    for row in 0..FIELD_TOP {
+        field[row * FIELD_WIDTH..(row + 1) * FIELD_WIDTH].reverse();
+    }
+}
+
+// the same as javascript's escape(), which fumen runs comments through
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped += &format!("%{:02X}", unit),
+            _ => escaped += &format!("%u{:04X}", unit),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |from: usize, to: usize| {
+            text.get(from..to)
+                .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+        };
+        if bytes[i] == b'%' {
+            if bytes.get(i + 1) == Some(&b'u') {
+                if let Some(unit) = hex(i + 2, i + 6) {
+                    units.push(unit);
+                    i += 6;
+                    continue;
+                }
+            } else if let Some(unit) = hex(i + 1, i + 3) {
+                units.push(unit);
+                i += 3;
+                continue;
+            }
+        }
+        units.push(bytes[i] as u16);
+        i += 1;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the cells of the first page's piece, in fumen coordinates
+    fn piece_cells_of(data: &str) -> Vec<(isize, isize)> {
+        let pages = decode(data).unwrap();
+        let page = &pages[0];
+        let piece = page.piece.as_ref().unwrap();
+        let mut cells: Vec<(isize, isize)> = piece
+            .get_coords()
+            .iter()
+            .map(|&(x, y)| flip_rows(x, y, page.board.height()))
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    // pieces on the floor of an empty field, the way fumen.zui.jp writes them
+    #[test]
+    fn decodes_pieces_where_fumen_puts_them() {
+        let o = vec![(4, 0), (4, 1), (5, 0), (5, 1)];
+        let i_flat = vec![(3, 0), (4, 0), (5, 0), (6, 0)];
+        let i_upright = vec![(4, 0), (4, 1), (4, 2), (4, 3)];
+        let s_flat = vec![(3, 0), (4, 0), (4, 1), (5, 1)];
+        let s_upright = vec![(4, 1), (4, 2), (5, 0), (5, 1)];
+        let z_flat = vec![(3, 1), (4, 0), (4, 1), (5, 0)];
+        let z_upright = vec![(4, 0), (4, 1), (5, 1), (5, 2)];
+        let cases = [
+            ("v115@vhATLJ", &o),
+            ("v115@vhALLJ", &o),
+            ("v115@vhADLJ", &o),
+            ("v115@vhAbLJ", &o),
+            ("v115@vhARQJ", &i_flat),
+            ("v115@vhABQJ", &i_flat),
+            ("v115@vhAJGJ", &i_upright),
+            ("v115@vhAZGJ", &i_upright),
+            ("v115@vhAXLJ", &s_flat),
+            ("v115@vhAHLJ", &s_flat),
+            ("v115@vhAvLJ", &s_upright),
+            ("v115@vhA/LJ", &s_upright),
+            ("v115@vhAULJ", &z_flat),
+            ("v115@vhAELJ", &z_flat),
+            ("v115@vhAMLJ", &z_upright),
+            ("v115@vhAcLJ", &z_upright),
+        ];
+        for (data, cells) in cases {
+            assert_eq!(&piece_cells_of(data), cells, "{}", data);
+        }
+        assert_eq!(
+            piece_cells_of("v115@vhAVQJ"),
+            vec![(3, 0), (4, 0), (4, 1), (5, 0)]
+        );
+        assert_eq!(
+            piece_cells_of("v115@vhA6JJ"),
+            vec![(0, 2), (1, 0), (1, 1), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn decodes_an_empty_page() {
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].piece.is_none());
+        assert!(pages[0]
+            .board
+            .grid
+            .iter()
+            .flatten()
+            .all(|&cell| cell == Tetromino::E));
+    }
+
+    #[test]
+    fn encodes_pieces_where_fumen_puts_them() {
+        let board = Board::new(FIELD_WIDTH, DEFAULT_HEIGHT);
+        let floor = board.height() as isize - 1;
+        let cells = [(4, floor), (5, floor), (4, floor - 1), (5, floor - 1)];
+        let page = FumenPage {
+            board,
+            piece: Some(Piece::from_cells(Tetromino::O, cells).unwrap()),
+            comment: String::new(),
+            lock: true,
+        };
+        assert_eq!(encode(&[page]), "v115@vhATLJ");
+    }
+
+    #[test]
+    fn round_trips_boards_pieces_and_comments() {
+        let mut board = Board::new(FIELD_WIDTH, DEFAULT_HEIGHT);
+        let floor = board.height() - 1;
+        for x in 0..FIELD_WIDTH - 1 {
+            board.grid[floor][x] = Tetromino::G;
+        }
+        board.grid[floor - 1][0] = Tetromino::L;
+        let z = [(3, 10), (4, 10), (4, 11), (5, 11)];
+        let pages = vec![
+            FumenPage {
+                board: board.clone(),
+                piece: Some(Piece::from_cells(Tetromino::Z, z).unwrap()),
+                comment: "100% done".to_string(),
+                lock: false,
+            },
+            FumenPage {
+                board: board.clone(),
+                piece: None,
+                comment: "100% done".to_string(),
+                lock: true,
+            },
+            FumenPage {
+                board: Board::new(FIELD_WIDTH, DEFAULT_HEIGHT),
+                piece: None,
+                comment: "\u{3042}".to_string(),
+                lock: true,
+            },
+        ];
+        let decoded = decode(&encode(&pages)).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (page, decoded) in pages.iter().zip(&decoded) {
+            assert!(page.board.grid == decoded.board.grid);
+            assert!(page.piece == decoded.piece);
+            assert_eq!(page.comment, decoded.comment);
+            assert_eq!(page.lock, decoded.lock);
+        }
+    }
+}
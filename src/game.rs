@@ -1,14 +1,17 @@
 use crate::constants::*;
-use macroquad::prelude::*;
+use ::rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use macroquad::color::Color;
+use std::collections::VecDeque;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GameState {
     Menu,
     Playing,
     GameOver,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Tetromino {
     E = 0, // Empty
     I,
@@ -18,6 +21,7 @@ pub enum Tetromino {
     Z,
     J,
     L,
+    G, // Garbage
 }
 
 impl Tetromino {
@@ -31,16 +35,47 @@ impl Tetromino {
             Tetromino::Z => Color::from_hex(0xF00000),
             Tetromino::J => Color::from_hex(0x0000F0),
             Tetromino::L => Color::from_hex(0xF0A000),
+            Tetromino::G => Color::from_hex(0x808080),
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Tetromino::E => '.',
+            Tetromino::I => 'I',
+            Tetromino::O => 'O',
+            Tetromino::T => 'T',
+            Tetromino::S => 'S',
+            Tetromino::Z => 'Z',
+            Tetromino::J => 'J',
+            Tetromino::L => 'L',
+            Tetromino::G => 'G',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Tetromino> {
+        match c {
+            '.' => Some(Tetromino::E),
+            'I' => Some(Tetromino::I),
+            'O' => Some(Tetromino::O),
+            'T' => Some(Tetromino::T),
+            'S' => Some(Tetromino::S),
+            'Z' => Some(Tetromino::Z),
+            'J' => Some(Tetromino::J),
+            'L' => Some(Tetromino::L),
+            'G' => Some(Tetromino::G),
+            _ => None,
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
-    tetromino: Tetromino,
-    x: isize,
-    y: isize,
-    orientation: Orientation,
+    pub tetromino: Tetromino,
+    pub x: isize,
+    pub y: isize,
+    pub orientation: Orientation,
 }
 
 impl Piece {
@@ -52,40 +87,52 @@ impl Piece {
             orientation: Orientation::Up,
         }
     }
+
+    // a new piece in the two rows just above the visible field of a board `width` cells wide,
+    // centered (rounding left)
+    pub fn spawn(tetromino: Tetromino, width: usize) -> Piece {
+        Piece {
+            x: (width as isize - 3) / 2,
+            y: HIDDEN_ROWS as isize - 2,
+            ..Piece::new(tetromino)
+        }
+    }
+
+    // cells relative to the top left of the piece's 4x4 box
     fn get_base_coords(&self) -> [(isize, isize); 4] {
         match self.tetromino {
             Tetromino::I => match self.orientation {
-                Orientation::Up | Orientation::Down => [(3, 0), (4, 0), (5, 0), (6, 0)],
-                Orientation::Right | Orientation::Left => [(5, 0), (5, 1), (5, 2), (5, 3)],
+                Orientation::Up | Orientation::Down => [(0, 0), (1, 0), (2, 0), (3, 0)],
+                Orientation::Right | Orientation::Left => [(2, 0), (2, 1), (2, 2), (2, 3)],
             },
-            Tetromino::O => [(4, 0), (5, 0), (4, 1), (5, 1)],
+            Tetromino::O => [(1, 0), (2, 0), (1, 1), (2, 1)],
             Tetromino::T => match self.orientation {
-                Orientation::Up => [(4, 0), (3, 1), (4, 1), (5, 1)],
-                Orientation::Right => [(4, 0), (5, 1), (4, 1), (4, 2)],
-                Orientation::Down => [(4, 1), (3, 0), (4, 0), (5, 0)],
-                Orientation::Left => [(4, 0), (3, 1), (4, 1), (4, 2)],
+                Orientation::Up => [(1, 0), (0, 1), (1, 1), (2, 1)],
+                Orientation::Right => [(1, 0), (2, 1), (1, 1), (1, 2)],
+                Orientation::Down => [(1, 1), (0, 0), (1, 0), (2, 0)],
+                Orientation::Left => [(1, 0), (0, 1), (1, 1), (1, 2)],
             },
             Tetromino::L => match self.orientation {
-                Orientation::Up => [(3, 1), (4, 1), (5, 1), (5, 0)],
-                Orientation::Right => [(4, 0), (4, 1), (4, 2), (5, 2)],
-                Orientation::Down => [(3, 0), (4, 0), (5, 0), (3, 1)],
-                Orientation::Left => [(3, 0), (4, 0), (4, 1), (4, 2)],
+                Orientation::Up => [(0, 1), (1, 1), (2, 1), (2, 0)],
+                Orientation::Right => [(1, 0), (1, 1), (1, 2), (2, 2)],
+                Orientation::Down => [(0, 0), (1, 0), (2, 0), (0, 1)],
+                Orientation::Left => [(0, 0), (1, 0), (1, 1), (1, 2)],
             },
             Tetromino::J => match self.orientation {
-                Orientation::Up => [(3, 1), (4, 1), (5, 1), (3, 0)],
-                Orientation::Right => [(4, 0), (4, 1), (4, 2), (5, 0)],
-                Orientation::Down => [(3, 0), (4, 0), (5, 0), (5, 1)],
-                Orientation::Left => [(4, 0), (4, 1), (4, 2), (3, 2)],
+                Orientation::Up => [(0, 1), (1, 1), (2, 1), (0, 0)],
+                Orientation::Right => [(1, 0), (1, 1), (1, 2), (2, 0)],
+                Orientation::Down => [(0, 0), (1, 0), (2, 0), (2, 1)],
+                Orientation::Left => [(1, 0), (1, 1), (1, 2), (0, 2)],
             },
             Tetromino::S => match self.orientation {
-                Orientation::Up | Orientation::Down => [(4, 0), (5, 0), (3, 1), (4, 1)],
-                Orientation::Right | Orientation::Left => [(4, 0), (4, 1), (5, 1), (5, 2)],
+                Orientation::Up | Orientation::Down => [(1, 0), (2, 0), (0, 1), (1, 1)],
+                Orientation::Right | Orientation::Left => [(1, 0), (1, 1), (2, 1), (2, 2)],
             },
             Tetromino::Z => match self.orientation {
-                Orientation::Up | Orientation::Down => [(3, 0), (4, 0), (4, 1), (5, 1)],
-                Orientation::Right | Orientation::Left => [(5, 0), (4, 1), (5, 1), (4, 2)],
+                Orientation::Up | Orientation::Down => [(0, 0), (1, 0), (1, 1), (2, 1)],
+                Orientation::Right | Orientation::Left => [(2, 0), (1, 1), (2, 1), (1, 2)],
             },
-            Tetromino::E => [(0, 0), (0, 0), (0, 0), (0, 0)], // useless case
+            Tetromino::E | Tetromino::G => [(0, 0), (0, 0), (0, 0), (0, 0)], // useless case
         }
     }
 
@@ -97,6 +144,36 @@ impl Piece {
         }
         coords
     }
+
+    // finds the piece (if any) that covers exactly these cells
+    pub fn from_cells(tetromino: Tetromino, cells: [(isize, isize); 4]) -> Option<Piece> {
+        let mut cells = cells;
+        cells.sort();
+        for orientation in [
+            Orientation::Up,
+            Orientation::Right,
+            Orientation::Down,
+            Orientation::Left,
+        ] {
+            let mut piece = Piece {
+                tetromino,
+                x: 0,
+                y: 0,
+                orientation,
+            };
+            let mut base_coords = piece.get_coords();
+            base_coords.sort();
+            piece.x = cells[0].0 - base_coords[0].0;
+            piece.y = cells[0].1 - base_coords[0].1;
+
+            let mut coords = piece.get_coords();
+            coords.sort();
+            if coords == cells {
+                return Some(piece);
+            }
+        }
+        None
+    }
 }
 
 impl Piece {
@@ -122,7 +199,8 @@ impl Piece {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Orientation {
     Up = 0,
     Right,
@@ -130,55 +208,128 @@ pub enum Orientation {
     Left,
 }
 
+// the ways a game can end (block out, lock out and garbage out)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TopOut {
+    // a new piece spawned on top of blocks already there
+    Block,
+    // a piece locked without any of it reaching the visible field
+    Lock,
+    // garbage pushed blocks off the top of the board
+    Garbage,
+}
+
+// what the last piece to lock did
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Lock {
+    pub lines: usize,
+    // a T that rotated into place with three of the corners around its centre filled
+    pub t_spin: bool,
+    // the lines it cleared left the board empty
+    pub perfect_clear: bool,
+}
+
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Move {
     Left,
     Right,
     Down,
     Rotate,
     Drop,
+    // swap the piece with the held one, handled by `Game` since it isn't a move on the board
+    Hold,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        into = "crate::serialization::BoardJson",
+        try_from = "crate::serialization::BoardJson"
+    )
+)]
 pub struct Board {
-    pub grid: [[Tetromino; WIDTH]; HEIGHT],
+    // rows from the top down, all the same length
+    // the first `HIDDEN_ROWS` are above the visible field
+    pub grid: Vec<Vec<Tetromino>>,
     pub score: u32,
     pub just_dropped: bool,
-    is_placed_time: Option<f64>,
+    // when the piece first touched down, used for the placement delay
+    pub is_placed_time: Option<f64>,
 }
 
 impl Board {
-    pub fn new() -> Board {
+    // `height` is the number of visible rows, the hidden ones are added on top
+    pub fn new(width: usize, height: usize) -> Board {
         Board {
-            grid: [[Tetromino::E; WIDTH]; HEIGHT],
+            grid: vec![vec![Tetromino::E; width]; height + HIDDEN_ROWS],
             score: 0,
             just_dropped: false,
             is_placed_time: None,
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.height() - HIDDEN_ROWS
+    }
+
+    // the rows that are drawn, from the top down
+    pub fn visible_rows(&self) -> &[Vec<Tetromino>] {
+        &self.grid[HIDDEN_ROWS..]
+    }
+
     pub fn add_piece(&mut self, piece: &Piece) {
         for &(x, y) in &piece.get_coords() {
             self.grid[y as usize][x as usize] = piece.tetromino;
         }
     }
 
-    fn remove_piece(&mut self, piece: &Piece) {
+    pub fn remove_piece(&mut self, piece: &Piece) {
         for &(x, y) in &piece.get_coords() {
             self.grid[y as usize][x as usize] = Tetromino::E;
         }
     }
 
+    // where a new piece goes, or None if blocks are in the way (block out)
+    // it drops into the visible field straight away if there's room
+    pub fn spawn_piece(&mut self, tetromino: Tetromino) -> Option<Piece> {
+        let piece = Piece::spawn(tetromino, self.width());
+        if self.is_colliding(&piece) {
+            return None;
+        }
+        let mut lowered = piece.clone();
+        lowered.move_down();
+        if self.is_colliding(&lowered) {
+            Some(piece)
+        } else {
+            Some(lowered)
+        }
+    }
+
     pub fn is_out_of_bounds(&self, piece: &Piece) -> bool {
         for &(x, y) in &piece.get_coords() {
-            if x < 0 || x >= WIDTH as isize || y >= HEIGHT as isize {
+            if x < 0 || x >= self.width() as isize || y < 0 || y >= self.height() as isize {
                 return true;
             }
         }
         false
     }
 
+    // cells off the board count as filled
     pub fn is_colliding(&mut self, piece: &Piece) -> bool {
+        if self.is_out_of_bounds(piece) {
+            return true;
+        }
         for &(x, y) in &piece.get_coords() {
             match self.grid[y as usize][x as usize] {
                 Tetromino::E => continue,
@@ -188,13 +339,13 @@ impl Board {
         false
     }
 
-    pub fn is_placed(&mut self, piece: &Piece) -> bool {
+    pub fn is_placed(&mut self, piece: &Piece, time: f64) -> bool {
         // first I'll have to remove the piece from the board
         self.remove_piece(piece);
         let mut is_placed = false;
         // then check if it can move down
         for &(x, y) in &piece.get_coords() {
-            if y == HEIGHT as isize - 1 {
+            if y == self.height() as isize - 1 {
                 is_placed = true;
                 break;
             }
@@ -210,10 +361,10 @@ impl Board {
         if is_placed {
             match self.is_placed_time {
                 None => {
-                    self.is_placed_time = Some(get_time()); // start the timer
+                    self.is_placed_time = Some(time); // start the timer
                 }
-                Some(time) => {
-                    if get_time() - time > PLACEMENT_DELAY {
+                Some(start_time) => {
+                    if time - start_time > PLACEMENT_DELAY {
                         self.is_placed_time = None;
                         return true;
                     }
@@ -229,12 +380,13 @@ impl Board {
     fn adjust_rotation(&mut self, piece: &mut Piece) {
         // check if the piece is out of bounds and the closest way to make it in bounds
         let mut x_offset = 0;
+        let width = self.width() as isize;
         let coords = piece.get_coords();
         for &(x, _) in &coords {
             if x < 0 && -x > x_offset {
                 x_offset = -x;
-            } else if (x >= WIDTH as isize) && -(x - (WIDTH as isize - 1)) < x_offset {
-                x_offset = -(x - (WIDTH as isize - 1));
+            } else if (x >= width) && -(x - (width - 1)) < x_offset {
+                x_offset = -(x - (width - 1));
             }
         }
         piece.x += x_offset;
@@ -257,6 +409,7 @@ impl Board {
                 // make sure to remove the final piece
                 self.remove_piece(&piece_copy);
             }
+            Move::Hold => (),
         }
         let can_move = !self.is_out_of_bounds(&piece_copy) && !self.is_colliding(&piece_copy);
         self.add_piece(piece);
@@ -279,6 +432,7 @@ impl Board {
                     self.adjust_rotation(piece);
                 }
                 Move::Drop => self.drop_piece(piece, true),
+                Move::Hold => (),
             }
             self.add_piece(piece);
         }
@@ -299,12 +453,14 @@ impl Board {
         self.is_placed_time = None;
     }
 
-    pub fn clear_lines(&mut self) {
+    // returns how many lines were cleared
+    pub fn clear_lines(&mut self) -> usize {
         let mut clears = 0;
-        let mut y = HEIGHT - 1;
+        let (width, height) = (self.width(), self.height());
+        let mut y = height - 1;
         while y > 0 {
             let mut is_clear = true;
-            for x in 0..WIDTH {
+            for x in 0..width {
                 if let Tetromino::E = self.grid[y][x] {
                     is_clear = false;
                     break;
@@ -312,13 +468,13 @@ impl Board {
             }
             if is_clear {
                 clears += 1;
-                if y == HEIGHT - 1 {
-                    for x in 0..WIDTH {
+                if y == height - 1 {
+                    for x in 0..width {
                         self.grid[y][x] = Tetromino::E;
                     }
                 }
                 for y2 in (1..=y).rev() {
-                    for x in 0..WIDTH {
+                    for x in 0..width {
                         self.grid[y2][x] = self.grid[y2 - 1][x];
                     }
                 }
@@ -327,55 +483,196 @@ impl Board {
             }
         }
 
-        match clears {
-            1 => self.score += 25,
-            2 => self.score += 100,
-            3 => self.score += 400,
-            4 => self.score += 1600,
-            _ => (),
-        }
+        self.score += clear_score(clears);
+        clears
     }
 
-    pub fn print(&self) {
-        for row in self.grid.iter() {
-            for &cell in row {
-                match cell {
-                    Tetromino::E => print!("."),
-                    _ => print!("X"),
-                }
+    // how many rows still have garbage in them
+    pub fn garbage_rows(&self) -> usize {
+        self.grid
+            .iter()
+            .filter(|row| row.contains(&Tetromino::G))
+            .count()
+    }
+
+    // brings up a garbage row under the stack for each hole given, `piece` being the active one
+    // the piece stays where it is unless the garbage runs into it, then it's pushed up too
+    pub fn insert_garbage(&mut self, piece: &mut Piece, holes: &[usize]) -> Result<(), TopOut> {
+        self.remove_piece(piece);
+        let mut pushed_out = false;
+        for &hole in holes {
+            pushed_out |= !self.push_garbage(hole);
+        }
+        while self.is_colliding(piece) {
+            if piece.get_coords().iter().any(|&(_, y)| y == 0) {
+                pushed_out = true;
+                break;
             }
-            println!();
+            piece.y -= 1;
+        }
+        self.add_piece(piece);
+        if pushed_out {
+            Err(TopOut::Garbage)
+        } else {
+            Ok(())
+        }
+    }
+
+    // pushes everything up a row and fills the bottom one with garbage, apart from `hole`
+    // (a hole past the right edge goes in the last column)
+    // returns false if a block was pushed off the top of the board
+    fn push_garbage(&mut self, hole: usize) -> bool {
+        let top = self.grid.remove(0);
+        let mut row = vec![Tetromino::G; top.len()];
+        row[hole.min(top.len() - 1)] = Tetromino::E;
+        self.grid.push(row);
+        top.iter().all(|&cell| cell == Tetromino::E)
+    }
+}
+
+// points for clearing this many lines at once
+pub fn clear_score(lines: usize) -> u32 {
+    match lines {
+        1 => 25,
+        2 => 100,
+        3 => 400,
+        4 => 1600,
+        _ => 0,
+    }
+}
+
+const TETROMINOS: [Tetromino; 7] = [
+    Tetromino::I,
+    Tetromino::O,
+    Tetromino::T,
+    Tetromino::S,
+    Tetromino::Z,
+    Tetromino::J,
+    Tetromino::L,
+];
+
+// how the piece chooser picks pieces
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Randomizer {
+    // each piece on its own, any of the seven
+    #[default]
+    Random,
+    // bags of all seven shuffled together (a 7-bag), so every run of seven pieces starting
+    // at a multiple of seven has one of each
+    Bag,
+}
+
+impl Randomizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Randomizer::Random => "random",
+            Randomizer::Bag => "bag",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Randomizer> {
+        match name {
+            "random" => Some(Randomizer::Random),
+            "bag" => Some(Randomizer::Bag),
+            _ => None,
         }
-        println!();
     }
 }
 
+#[derive(Clone)]
 pub struct PieceChooser {
     pub next_pieces: Vec<Tetromino>,
     max_next_pieces: usize,
-    // TODO: currently doesn't do anything
     seed: u64,
+    randomizer: Randomizer,
+    rng: StdRng,
+    // how many pieces have been taken from the rng, so its state can be rebuilt from the seed
+    draws: u64,
+    // what's left of the current bag, taken from the back
+    bag: Vec<Tetromino>,
+    // fixed pieces handed out before the rng is used (for puzzles)
+    sequence: VecDeque<Tetromino>,
 }
 
 impl PieceChooser {
-    pub fn new(max_next_pieces: usize) -> PieceChooser {
-        PieceChooser {
-            next_pieces: (0..max_next_pieces)
-                .map(|_| PieceChooser::get_random_piece())
-                .collect(),
+    pub fn new(max_next_pieces: usize, seed: u64, randomizer: Randomizer) -> PieceChooser {
+        PieceChooser::with_sequence(max_next_pieces, seed, randomizer, &[])
+    }
+
+    // hands out `sequence` in order before carrying on with pieces picked from the seed
+    pub fn with_sequence(
+        max_next_pieces: usize,
+        seed: u64,
+        randomizer: Randomizer,
+        sequence: &[Tetromino],
+    ) -> PieceChooser {
+        let mut chooser = PieceChooser {
+            next_pieces: Vec::new(),
             max_next_pieces,
-            seed: 0,
+            seed,
+            randomizer,
+            rng: StdRng::seed_from_u64(seed),
+            draws: 0,
+            bag: Vec::new(),
+            sequence: sequence.iter().copied().collect(),
+        };
+        // the queue comes out from the back
+        while chooser.next_pieces.len() < max_next_pieces {
+            let piece = chooser.draw();
+            chooser.next_pieces.insert(0, piece);
         }
+        chooser
+    }
+
+    // rebuilds a piece chooser that has already handed out `draws` pieces
+    pub fn restore(
+        seed: u64,
+        randomizer: Randomizer,
+        draws: u64,
+        next_pieces: Vec<Tetromino>,
+    ) -> PieceChooser {
+        let mut chooser = PieceChooser {
+            max_next_pieces: next_pieces.len(),
+            next_pieces,
+            seed,
+            randomizer,
+            rng: StdRng::seed_from_u64(seed),
+            draws: 0,
+            bag: Vec::new(),
+            sequence: VecDeque::new(),
+        };
+        while chooser.draws < draws {
+            chooser.draw();
+        }
+        chooser
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn randomizer(&self) -> Randomizer {
+        self.randomizer
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+
+    // how many pieces from the rng have come out of the queue, none until the fixed
+    // sequence has all come out
+    pub fn bag_pieces_out(&self) -> u64 {
+        self.draws.saturating_sub(self.next_pieces.len() as u64)
     }
 
     pub fn get_next_piece(&mut self) -> Tetromino {
-        let new_shape = PieceChooser::get_random_piece();
+        let new_shape = self.draw();
         let next_shape = if self.next_pieces.len() == self.max_next_pieces {
             self.next_pieces.pop().unwrap()
         } else {
             // this branch should technically never be reached
             // but I'll include this as a failsafe
-            PieceChooser::get_random_piece()
+            self.draw()
         };
 
         self.next_pieces.insert(0, new_shape);
@@ -383,16 +680,295 @@ impl PieceChooser {
         next_shape
     }
 
-    fn get_random_piece() -> Tetromino {
-        *rand::ChooseRandom::choose(&vec![
-            Tetromino::I,
-            Tetromino::O,
-            Tetromino::T,
-            Tetromino::S,
-            Tetromino::Z,
-            Tetromino::J,
-            Tetromino::L,
-        ])
-        .unwrap()
+    fn draw(&mut self) -> Tetromino {
+        if let Some(piece) = self.sequence.pop_front() {
+            return piece;
+        }
+        self.draws += 1;
+        match self.randomizer {
+            Randomizer::Random => *TETROMINOS.choose(&mut self.rng).unwrap(),
+            Randomizer::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = TETROMINOS.to_vec();
+                    self.bag.shuffle(&mut self.rng);
+                }
+                self.bag.pop().unwrap()
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub board: Board,
+    pub piece: Piece,
+    pub piece_chooser: PieceChooser,
+    pub num_tetrominos: usize,
+    // lines cleared so far
+    pub lines: usize,
+    // how many singles, doubles, triples and tetrises were cleared
+    pub clears: [usize; 4],
+    pub held: Option<Tetromino>,
+    // only one hold is allowed per piece
+    pub can_hold: bool,
+    // the last move that actually moved the piece, None for a freshly spawned piece
+    pub last_move: Option<Move>,
+    pub last_lock: Lock,
+}
+
+impl Game {
+    pub fn new(seed: u64, width: usize, height: usize) -> Game {
+        Game::with_board(seed, Randomizer::Random, Board::new(width, height))
+    }
+
+    // starts a game on a board that already has some cells filled in
+    pub fn with_board(seed: u64, randomizer: Randomizer, board: Board) -> Game {
+        Game::with_chooser(board, PieceChooser::new(NUM_NEXT_PIECES, seed, randomizer))
+    }
+
+    pub fn with_chooser(board: Board, piece_chooser: PieceChooser) -> Game {
+        let mut piece_chooser = piece_chooser;
+        let mut board = board;
+        let tetromino = piece_chooser.get_next_piece();
+        // a board that's already topped out is left for the first lock to notice
+        let piece = board
+            .spawn_piece(tetromino)
+            .unwrap_or_else(|| Piece::spawn(tetromino, board.width()));
+        board.add_piece(&piece);
+
+        Game {
+            board,
+            piece,
+            piece_chooser,
+            num_tetrominos: 0,
+            lines: 0,
+            clears: [0; 4],
+            held: None,
+            can_hold: true,
+            last_move: None,
+            last_lock: Lock::default(),
+        }
+    }
+
+    pub fn move_piece(&mut self, mov: Move) {
+        match mov {
+            Move::Hold => self.hold_piece(),
+            _ => {
+                let before = self.piece.clone();
+                self.board.move_piece(&mut self.piece, mov);
+                if self.piece != before {
+                    self.last_move = Some(mov);
+                }
+            }
+        }
+    }
+
+    fn hold_piece(&mut self) {
+        if !self.can_hold {
+            return;
+        }
+        // the piece coming out is either the held one or the next one in the queue
+        let next_shape = match self.held {
+            Some(held) => held,
+            None => *self.piece_chooser.next_pieces.last().unwrap(),
+        };
+
+        self.board.remove_piece(&self.piece);
+        let Some(next_piece) = self.board.spawn_piece(next_shape) else {
+            self.board.add_piece(&self.piece);
+            return;
+        };
+        if self.held.is_none() {
+            self.piece_chooser.get_next_piece();
+        }
+
+        self.held = Some(self.piece.tetromino);
+        self.piece = next_piece;
+        self.board.add_piece(&self.piece);
+        self.board.is_placed_time = None;
+        self.can_hold = false;
+        self.last_move = None;
+    }
+
+    // three-corner rule: the last move was a rotation and at least three of the
+    // cells diagonal to the T's centre are filled or off the board
+    fn is_t_spin(&self) -> bool {
+        if self.piece.tetromino != Tetromino::T || !matches!(self.last_move, Some(Move::Rotate)) {
+            return false;
+        }
+        let coords = self.piece.get_coords();
+        // the centre is the cell touching the other three
+        let Some(&(cx, cy)) = coords.iter().find(|&&(x, y)| {
+            coords
+                .iter()
+                .filter(|&&(ox, oy)| (ox - x).abs() + (oy - y).abs() == 1)
+                .count()
+                == 3
+        }) else {
+            return false;
+        };
+        let filled = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .iter()
+            .filter(|&&(dx, dy)| {
+                let (x, y) = (cx + dx, cy + dy);
+                x < 0
+                    || x >= self.board.width() as isize
+                    || y >= self.board.height() as isize
+                    || (y >= 0 && self.board.grid[y as usize][x as usize] != Tetromino::E)
+            })
+            .count();
+        filled >= 3
+    }
+
+    // clears any full lines left by the placed piece and brings in the next one
+    // returns how the game ended if the placed or new piece tops out
+    pub fn spawn_next_piece(&mut self) -> Result<(), TopOut> {
+        let locked_hidden = self
+            .piece
+            .get_coords()
+            .iter()
+            .all(|&(_, y)| y < HIDDEN_ROWS as isize);
+        if locked_hidden {
+            // nothing was cleared, so nothing from the last lock carries over
+            self.last_lock = Lock::default();
+            return Err(TopOut::Lock);
+        }
+        let t_spin = self.is_t_spin();
+        let cleared = self.board.clear_lines();
+        if cleared > 0 {
+            self.lines += cleared;
+            self.clears[cleared - 1] += 1;
+        }
+        self.last_lock = Lock {
+            lines: cleared,
+            t_spin,
+            perfect_clear: cleared > 0
+                && self
+                    .board
+                    .grid
+                    .iter()
+                    .flatten()
+                    .all(|&cell| cell == Tetromino::E),
+        };
+
+        let next_shape = self.piece_chooser.get_next_piece();
+        let Some(piece) = self.board.spawn_piece(next_shape) else {
+            // kept off the board, but there in case the game carries on after a clear out
+            self.piece = Piece::spawn(next_shape, self.board.width());
+            return Err(TopOut::Block);
+        };
+        self.piece = piece;
+        self.board.add_piece(&self.piece);
+
+        self.board.just_dropped = false;
+        self.can_hold = true;
+        self.last_move = None;
+        self.num_tetrominos += 1;
+        Ok(())
+    }
+
+    // empties the board after a top out and brings the piece that didn't fit back in at the top
+    pub fn clear_board(&mut self) {
+        for cell in self.board.grid.iter_mut().flatten() {
+            *cell = Tetromino::E;
+        }
+        let tetromino = self.piece.tetromino;
+        self.piece = self
+            .board
+            .spawn_piece(tetromino)
+            .unwrap_or_else(|| Piece::spawn(tetromino, self.board.width()));
+        self.board.add_piece(&self.piece);
+        self.board.just_dropped = false;
+        self.board.is_placed_time = None;
+        self.can_hold = true;
+        self.last_move = None;
+    }
+
+    // adds a garbage row under the stack for each hole given
+    pub fn add_garbage(&mut self, holes: &[usize]) -> Result<(), TopOut> {
+        self.board.insert_garbage(&mut self.piece, holes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a game on an empty 10x20 board with this piece falling
+    fn game_with(tetromino: Tetromino) -> Game {
+        let chooser = PieceChooser::with_sequence(1, 0, Randomizer::Random, &[tetromino]);
+        Game::with_chooser(Board::new(10, 20), chooser)
+    }
+
+    #[test]
+    fn pieces_off_the_board_are_colliding() {
+        let mut game = game_with(Tetromino::I);
+        let mut piece = game.piece.clone();
+        piece.y = -1;
+        assert!(game.board.is_colliding(&piece));
+        piece.y = 0;
+        piece.x = -1;
+        assert!(game.board.is_colliding(&piece));
+    }
+
+    #[test]
+    fn a_piece_spawning_on_blocks_is_a_block_out() {
+        let mut game = game_with(Tetromino::O);
+        game.board.remove_piece(&game.piece);
+        // where the next piece comes out, with a gap so the rows don't clear
+        for y in HIDDEN_ROWS - 2..HIDDEN_ROWS + 1 {
+            for x in 0..9 {
+                game.board.grid[y][x] = Tetromino::G;
+            }
+        }
+        game.piece.x = 7;
+        game.piece.y = 10;
+        game.board.add_piece(&game.piece);
+        assert_eq!(game.spawn_next_piece(), Err(TopOut::Block));
+    }
+
+    #[test]
+    fn a_piece_locking_above_the_field_is_a_lock_out() {
+        let mut game = game_with(Tetromino::O);
+        game.board.remove_piece(&game.piece);
+        game.piece.y = 0;
+        game.board.add_piece(&game.piece);
+        assert_eq!(game.spawn_next_piece(), Err(TopOut::Lock));
+    }
+
+    #[test]
+    fn garbage_comes_up_under_the_stack() {
+        let mut game = game_with(Tetromino::O);
+        game.add_garbage(&[3, 12]).unwrap();
+        let bottom = game.board.height() - 1;
+        let row = |y: usize| -> String { game.board.grid[y].iter().map(|c| c.to_char()).collect() };
+        // the newest row is at the bottom, and a hole off the board goes in the last column
+        assert_eq!(row(bottom), "GGGGGGGGG.");
+        assert_eq!(row(bottom - 1), "GGG.GGGGGG");
+        assert_eq!(game.board.garbage_rows(), 2);
+    }
+
+    #[test]
+    fn garbage_pushes_the_active_piece_up() {
+        let mut game = game_with(Tetromino::O);
+        game.move_piece(Move::Drop);
+        let dropped = game.piece.clone();
+        game.add_garbage(&[0, 0]).unwrap();
+        assert_eq!(game.piece.x, dropped.x);
+        assert_eq!(game.piece.y, dropped.y - 2);
+        // and it's still drawn on the board, just above the garbage
+        for (x, y) in game.piece.get_coords() {
+            assert_eq!(game.board.grid[y as usize][x as usize], Tetromino::O);
+        }
+    }
+
+    #[test]
+    fn garbage_that_pushes_blocks_off_the_top_tops_out() {
+        let mut game = game_with(Tetromino::O);
+        // a column of blocks up to the very top row, away from the piece
+        for y in 0..game.board.height() {
+            game.board.grid[y][0] = Tetromino::G;
+        }
+        assert_eq!(game.add_garbage(&[5]), Err(TopOut::Garbage));
     }
 }
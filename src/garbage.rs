@@ -0,0 +1,48 @@
+// picks where the holes in garbage rows go
+// seeded from the game so the same seed always digs through the same garbage
+
+use ::rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub struct GarbageGenerator {
+    rng: StdRng,
+    // the chance (from 0 to 1) that a row's hole isn't lined up with the one below it
+    messiness: f64,
+    prev_hole: Option<usize>,
+    // how many holes have been picked, so a saved game can pick up where it was
+    holes: u64,
+}
+
+impl GarbageGenerator {
+    pub fn new(seed: u64, messiness: f64) -> GarbageGenerator {
+        GarbageGenerator {
+            rng: StdRng::seed_from_u64(seed),
+            messiness: messiness.clamp(0.0, 1.0),
+            prev_hole: None,
+            holes: 0,
+        }
+    }
+
+    // a generator that has already picked `holes` holes for a board `width` cells wide
+    pub fn restore(seed: u64, messiness: f64, holes: u64, width: usize) -> GarbageGenerator {
+        let mut generator = GarbageGenerator::new(seed, messiness);
+        while generator.holes < holes {
+            generator.next_hole(width);
+        }
+        generator
+    }
+
+    pub fn holes(&self) -> u64 {
+        self.holes
+    }
+
+    // the hole for the next row on a board `width` cells wide
+    pub fn next_hole(&mut self, width: usize) -> usize {
+        let hole = match self.prev_hole {
+            Some(hole) if hole < width && !self.rng.gen_bool(self.messiness) => hole,
+            _ => self.rng.gen_range(0..width),
+        };
+        self.prev_hole = Some(hole);
+        self.holes += 1;
+        hole
+    }
+}
@@ -0,0 +1,142 @@
+// a bot that tries every placement of the current and held piece and picks the one that
+// leaves the best looking board
+//
+// boards are judged on a few features, each weighted and added up (the default weights
+// are the ones from Yiyuan Lee's "Tetris AI: the (near) perfect bot")
+
+use std::cell::RefCell;
+
+use crate::constants::*;
+use crate::game::*;
+use crate::movegen::{placements, Placement};
+use crate::player::{Player, Thoughts};
+
+// what a board looks like after a placement
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Features {
+    // the heights of all the columns added up
+    pub height: f64,
+    // lines the placement cleared
+    pub lines: f64,
+    // empty cells with a filled one somewhere above them
+    pub holes: f64,
+    // how much the heights of neighbouring columns differ, added up
+    pub bumpiness: f64,
+}
+
+impl Features {
+    pub fn new(board: &Board, lines: usize) -> Features {
+        let heights: Vec<usize> = (0..board.width())
+            .map(|x| {
+                let top = board.grid.iter().position(|row| row[x] != Tetromino::E);
+                top.map_or(0, |y| board.height() - y)
+            })
+            .collect();
+        let holes = (0..board.width())
+            .map(|x| {
+                let top = board.height() - heights[x];
+                board.grid[top..]
+                    .iter()
+                    .filter(|row| row[x] == Tetromino::E)
+                    .count()
+            })
+            .sum::<usize>();
+        let bumpiness = heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum::<usize>();
+        Features {
+            height: heights.iter().sum::<usize>() as f64,
+            lines: lines as f64,
+            holes: holes as f64,
+            bumpiness: bumpiness as f64,
+        }
+    }
+
+    // each feature's name, value and weighted part of the score, in the order they're declared
+    pub fn terms(&self, weights: &Features) -> [(&'static str, f64, f64); 4] {
+        [
+            ("height", self.height, weights.height),
+            ("lines", self.lines, weights.lines),
+            ("holes", self.holes, weights.holes),
+            ("bumpiness", self.bumpiness, weights.bumpiness),
+        ]
+        .map(|(name, value, weight)| (name, value, value * weight))
+    }
+
+    // higher is better
+    pub fn score(&self, weights: &Features) -> f64 {
+        self.terms(weights).iter().map(|(_, _, term)| term).sum()
+    }
+}
+
+pub struct HeuristicPlayer {
+    // how much each feature counts for, as a `Features` so each lines up with its feature
+    pub weights: Features,
+    thoughts: RefCell<Option<Thoughts>>,
+}
+
+impl Default for HeuristicPlayer {
+    fn default() -> HeuristicPlayer {
+        HeuristicPlayer {
+            weights: Features {
+                height: -0.510066,
+                lines: 0.760666,
+                holes: -0.35663,
+                bumpiness: -0.184483,
+            },
+            thoughts: RefCell::new(None),
+        }
+    }
+}
+
+impl HeuristicPlayer {
+    // every placement with what it does to the board and its score, best first
+    pub fn rank(&self, game: &Game) -> Vec<(Placement, Features, f64)> {
+        let mut ranked: Vec<_> = placements(game)
+            .into_iter()
+            .map(|placement| {
+                let (board, lines) = placement.result(game);
+                let features = Features::new(&board, lines);
+                let score = features.score(&self.weights);
+                (placement, features, score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.2.total_cmp(&a.2));
+        ranked
+    }
+}
+
+impl Player for HeuristicPlayer {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        let ranked = self.rank(game);
+        let Some((best, features, _)) = ranked.first() else {
+            // nowhere to go, the game is about to be lost anyway
+            return vec![Move::Drop];
+        };
+
+        let mut path = vec![game.piece.clone()];
+        let mut moved = game.clone();
+        for &mov in &best.moves {
+            moved.move_piece(mov);
+            if path.last() != Some(&moved.piece) {
+                path.push(moved.piece.clone());
+            }
+        }
+        *self.thoughts.borrow_mut() = Some(Thoughts {
+            pieces: game.num_tetrominos,
+            candidates: ranked
+                .iter()
+                .take(SHOWN_CANDIDATES)
+                .map(|(placement, _, score)| (placement.piece.clone(), *score))
+                .collect(),
+            path,
+            breakdown: features.terms(&self.weights).to_vec(),
+        });
+        best.moves.clone()
+    }
+
+    fn thoughts(&self) -> Option<Thoughts> {
+        self.thoughts.borrow().clone()
+    }
+}
@@ -1,141 +1,619 @@
-use macroquad::{miniquad::window::set_window_size, prelude::*};
-use std::{thread::sleep, time::SystemTime};
+use macroquad::{miniquad::window::set_window_size, prelude::*, Window};
+use std::thread::sleep;
 
+mod args;
+mod attack;
 mod constants;
 mod draw;
+#[cfg(feature = "serde")]
+mod external;
+mod finesse;
+mod fumen;
 mod game;
+mod garbage;
+mod heuristic;
+mod mode;
+mod movegen;
+mod netplay;
+mod notation;
+mod opener;
+mod pc;
 mod player;
+mod puzzle;
+mod rating;
+mod records;
+mod replay;
+mod save;
+#[cfg(feature = "serde")]
+mod serialization;
+mod session;
+#[cfg(feature = "serde")]
+mod tbp;
+mod tournament;
+mod tui;
+mod versus;
+mod worker;
 
+use args::Args;
+use attack::AttackTable;
 use constants::*;
 use draw::*;
 use game::*;
-use player::{Player, RandomPlayer};
+use mode::Mode;
+use netplay::{Connection, Hello, NetMatch};
+use opener::Trainer;
+use player::Player;
+use replay::{Replay, ReplayPlayer, Ruleset};
+use save::SavedGame;
+use session::Session;
+use versus::Versus;
+use worker::BotWorker;
 
-const MANUAL: bool = false;
+fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    if let Some(games) = args.tournament {
+        if let Err(err) = tournament::run(&args, games, &mut |line| println!("{}", line)) {
+            println!("Couldn't run the tournament: {}", err);
+        }
+        return;
+    }
+
+    if let Some(lines) = args.solve_pc {
+        if let Err(err) = pc::print_solutions(&args, lines) {
+            println!("{}", err);
+        }
+        return;
+    }
+
+    if args.tui {
+        if let Err(err) = tui::run(&args) {
+            println!("Terminal error: {}", err);
+        }
+        return;
+    }
+
+    if let Some(path) = &args.replay {
+        match Replay::load(path) {
+            Ok(replay)
+                if replay.ruleset
+                    != Ruleset::current(
+                        replay.ruleset.width,
+                        replay.ruleset.height,
+                        replay.ruleset.randomizer,
+                    ) =>
+            {
+                println!(
+                    "Replay was recorded with different rules: {:?}",
+                    replay.ruleset
+                );
+            }
+            Ok(replay) => Window::new("Tetris", play_replay(replay)),
+            Err(err) => println!("Couldn't load replay: {}", err),
+        }
+        return;
+    }
+
+    if args.host.is_some() || args.join.is_some() {
+        match connect(&args) {
+            Ok((connection, hello)) => Window::new("Tetris", run_network(args, connection, hello)),
+            Err(err) => println!("Couldn't connect: {}", err),
+        }
+        return;
+    }
 
-#[macroquad::main("Tetris")]
-async fn main() {
+    if args.mode == Mode::Versus {
+        Window::new("Tetris", run_versus(args));
+        return;
+    }
+
+    Window::new("Tetris", run_window(args));
+}
+
+// the keys for each move when one person is playing
+const MOVE_KEYS: [(KeyCode, Move); 6] = [
+    (KeyCode::Up, Move::Rotate),
+    (KeyCode::Down, Move::Down),
+    (KeyCode::Right, Move::Right),
+    (KeyCode::Left, Move::Left),
+    (KeyCode::Space, Move::Drop),
+    (KeyCode::C, Move::Hold),
+];
+
+// the keys for each side when two people share the keyboard in versus mode
+const VERSUS_KEYS: [[(KeyCode, Move); 6]; 2] = [
+    [
+        (KeyCode::W, Move::Rotate),
+        (KeyCode::S, Move::Down),
+        (KeyCode::D, Move::Right),
+        (KeyCode::A, Move::Left),
+        (KeyCode::Space, Move::Drop),
+        (KeyCode::Q, Move::Hold),
+    ],
+    [
+        (KeyCode::Up, Move::Rotate),
+        (KeyCode::Down, Move::Down),
+        (KeyCode::Right, Move::Right),
+        (KeyCode::Left, Move::Left),
+        (KeyCode::Enter, Move::Drop),
+        (KeyCode::RightShift, Move::Hold),
+    ],
+];
+
+fn pressed_moves(keys: &[(KeyCode, Move)]) -> Vec<Move> {
+    keys.iter()
+        .filter(|(key, _)| is_key_pressed(*key))
+        .map(|&(_, mov)| mov)
+        .collect()
+}
+
+// fits the board, its walls and the side panel
+fn set_board_window_size(width: usize, height: usize) {
     set_window_size(
-        ((WIDTH + 2) as f32 * SQUARE_SIZE) as u32 + SIDE_PANEL_WIDTH as u32,
-        ((HEIGHT + 3) as f32 * SQUARE_SIZE) as u32,
+        ((width + 2) as f32 * SQUARE_SIZE) as u32 + SIDE_PANEL_WIDTH as u32,
+        ((height + 3) as f32 * SQUARE_SIZE) as u32,
     );
+}
 
-    let mut game_state = GameState::Menu;
-
-    let mut piece_chooser = PieceChooser::new(3);
-    let player = RandomPlayer;
-    let mut chosen_moves = Vec::new();
+async fn run_window(args: Args) {
+    set_board_window_size(args.width, args.height);
 
-    let mut board = Board::new();
-    let mut next_shape = piece_chooser.get_next_piece();
-    let mut piece = Piece::new(next_shape);
+    let mut game_state = GameState::Menu;
+    // whether what the bot is thinking is drawn over the board, toggled with D
+    let mut show_thoughts = false;
+    // shows where the opener being practised has each piece go, when playing by hand
+    let mut trainer = args
+        .opener
+        .as_ref()
+        .filter(|_| args.manual)
+        .map(|name| Trainer::new(opener::find(name)));
+    // the left or right key being held down, when it was pressed and for which piece, so it
+    // can shift that piece to the wall once it's been held long enough
+    let mut das: Option<(KeyCode, Move, f64, usize)> = None;
 
-    board.add_piece(&piece);
-    let drop_time = 1.0;
-    let mut prev_time = get_time();
+    let player = match BotWorker::from_args(&args) {
+        Ok(player) => player,
+        Err(err) => {
+            println!("Couldn't start the bot: {}", err);
+            return;
+        }
+    };
 
-    let mut num_tetrominos = 0;
+    let mut session = match Session::from_args(&args, get_time()) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    if args.resume.is_some() {
+        game_state = GameState::Playing;
+    }
 
     loop {
-        set_window_size(
-            ((WIDTH + 2) as f32 * SQUARE_SIZE) as u32 + SIDE_PANEL_WIDTH as u32,
-            ((HEIGHT + 3) as f32 * SQUARE_SIZE) as u32,
+        // loaded boards and saves can be a different size from the one asked for
+        let (width, height) = (
+            session.game.board.width(),
+            session.game.board.visible_height(),
         );
+        set_board_window_size(width, height);
         clear_background(Color::from_rgba(40, 40, 40, 255));
         match game_state {
             GameState::Menu => {
-                draw_menu();
+                draw_menu(width, height);
                 if is_key_pressed(KeyCode::Space) {
                     game_state = GameState::Playing;
+                    session.start(get_time());
+                }
+                if is_key_pressed(KeyCode::L) {
+                    match SavedGame::load(&args.save) {
+                        Ok(saved) => {
+                            session.resume(saved, args.manual, get_time());
+                            game_state = GameState::Playing;
+                        }
+                        Err(err) => println!("Couldn't load saved game: {}", err),
+                    }
                 }
             }
             GameState::Playing => {
-                if num_tetrominos >= TETROMINO_LIMIT {
+                if session.is_out_of_pieces() {
                     println!("Out of pieces!");
                     break;
                 }
 
-                // check if the previous piece is placed
-                if board.is_placed(&piece) || board.just_dropped {
-                    board.clear_lines();
-
-                    // get the next piece
-                    next_shape = piece_chooser.get_next_piece();
-                    piece = Piece::new(next_shape);
-
-                    // check collision with the new piece
-                    if board.is_colliding(&piece) {
-                        game_state = GameState::GameOver;
-                        continue;
+                if is_key_pressed(KeyCode::S) {
+                    match session.save(&args.save, get_time()) {
+                        Ok(()) => println!("Game saved to {}", args.save.display()),
+                        Err(err) => println!("Couldn't save game: {}", err),
                     }
-                    board.add_piece(&piece);
-
-                    board.just_dropped = false;
-
-                    num_tetrominos += 1;
-                }
-
-                if get_time() - prev_time > drop_time {
-                    board.move_piece(&mut piece, Move::Down);
-                    prev_time = get_time();
                 }
 
-                if MANUAL {
-                    if is_key_pressed(KeyCode::Up) {
-                        board.move_piece(&mut piece, Move::Rotate);
+                let mut moves = Vec::new();
+                if args.manual {
+                    moves = pressed_moves(&MOVE_KEYS);
+                    for (key, mov) in [(KeyCode::Left, Move::Left), (KeyCode::Right, Move::Right)] {
+                        if is_key_pressed(key) {
+                            das = Some((key, mov, get_time(), session.game.num_tetrominos));
+                        }
                     }
-                    if is_key_pressed(KeyCode::Down) {
-                        board.move_piece(&mut piece, Move::Down);
+                    if let Some((key, mov, pressed, piece)) = das {
+                        if !is_key_down(key) {
+                            das = None;
+                        } else if get_time() - pressed >= DAS_DELAY
+                            && piece == session.game.num_tetrominos
+                        {
+                            session.shift_to_wall(get_time(), mov);
+                        }
                     }
-                    if is_key_pressed(KeyCode::Right) {
-                        board.move_piece(&mut piece, Move::Right);
+                    if is_key_pressed(KeyCode::G) {
+                        session.add_garbage(get_time());
                     }
-                    if is_key_pressed(KeyCode::Left) {
-                        board.move_piece(&mut piece, Move::Left);
+                    if is_key_pressed(KeyCode::Z) {
+                        session.undo();
                     }
-                    if is_key_pressed(KeyCode::Space) {
-                        board.move_piece(&mut piece, Move::Drop);
+                    if is_key_pressed(KeyCode::Y) {
+                        session.redo();
                     }
-                } else {
-                    if chosen_moves.len() > 0 {
-                        board.move_piece(&mut piece, chosen_moves[0]);
-                        chosen_moves.remove(0);
-                    } else {
-                        chosen_moves = player.choose_moves(&board, &piece);
+                    let slots = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3];
+                    for (slot, key) in slots.into_iter().enumerate() {
+                        if is_key_pressed(key) {
+                            session.cycle_next_piece(slot);
+                        }
                     }
+                } else if is_key_pressed(KeyCode::D) {
+                    show_thoughts = !show_thoughts;
                 }
+                let bot = (!args.manual).then_some(&player as &dyn Player);
+                session.update(get_time(), &moves, bot);
 
-                draw_tetris_grid(WIDTH, HEIGHT);
-                for y in 0..HEIGHT {
-                    for x in 0..WIDTH {
-                        draw_tetro(&board.grid[y][x], x, y);
+                if session.game_over {
+                    for message in session.finish(&args) {
+                        println!("{}", message);
                     }
+                    game_state = GameState::GameOver;
+                    continue;
                 }
 
-                draw_score(board.score);
-                draw_next_pieces(&piece_chooser.next_pieces);
+                draw_tetris_grid(width, height, MARGIN_LEFT);
+                draw_board(&session.game.board, MARGIN_LEFT);
+                if let Some(hint) = trainer.as_mut().and_then(|t| t.hint(&session.game)) {
+                    draw_hint(&hint, MARGIN_LEFT);
+                }
+                // only once the bot has thought about the piece that's falling
+                let thoughts = player
+                    .thoughts()
+                    .filter(|thoughts| thoughts.pieces == session.game.num_tetrominos);
+                if let Some(thoughts) = thoughts.filter(|_| show_thoughts) {
+                    draw_thoughts(&thoughts, MARGIN_LEFT);
+                }
+
+                draw_score(session.game.board.score, width, MARGIN_LEFT);
+                draw_next_pieces(&session.game.piece_chooser.next_pieces, width, MARGIN_LEFT);
+                draw_hold(session.game.held, width, MARGIN_LEFT);
+                let mut stats = session.stats(get_time());
+                if args.manual {
+                    stats.extend(session.finesse.stats());
+                } else {
+                    stats.extend(player.stats());
+                }
+                stats.extend(trainer.as_ref().map(Trainer::status));
+                draw_stats(&stats, width, MARGIN_LEFT);
             }
             GameState::GameOver => {
-                draw_game_over(board.score);
+                draw_game_over(session.title(), session.game.board.score, width, height);
+                draw_results(&session.results(), width, height);
 
                 if is_key_pressed(KeyCode::Space) {
                     game_state = GameState::Playing;
-                    board = Board::new();
-                    next_shape = piece_chooser.get_next_piece();
-                    piece = Piece::new(next_shape);
-                    board.add_piece(&piece);
-                    prev_time = get_time();
-                    num_tetrominos = 0;
+                    session.restart(get_time());
                 }
             }
         }
         // add a delay to the game loop if not in manual mode
-        if !MANUAL {
+        if !args.manual {
+            sleep(std::time::Duration::from_millis(100));
+        }
+
+        next_frame().await
+    }
+
+    for message in session.finish(&args) {
+        println!("{}", message);
+    }
+    println!("Final score: {}", session.game.board.score);
+}
+
+// how far across the window each side's board starts in versus mode
+fn versus_left(side: usize, width: usize) -> f32 {
+    let slot_width = (width + 2) as f32 * SQUARE_SIZE + SIDE_PANEL_WIDTH;
+    side as f32 * slot_width + MARGIN_LEFT
+}
+
+async fn run_versus(args: Args) {
+    let table = match &args.attack_table {
+        Some(path) => match AttackTable::load(path) {
+            Ok(table) => table,
+            Err(err) => {
+                println!("Couldn't load attack table: {}", err);
+                return;
+            }
+        },
+        None => AttackTable::default(),
+    };
+    let seed = args.seed.unwrap_or_else(::rand::random);
+    let (width, height) = (args.width, args.height);
+    let mut versus = Versus::new(seed, args.randomizer, width, height, table, get_time());
+    // each bot gets its own, since some keep track of the game they're playing
+    let mut players = Vec::new();
+    for human in args.humans {
+        players.push(match human {
+            true => None,
+            false => match BotWorker::from_args(&args) {
+                Ok(player) => Some(player),
+                Err(err) => {
+                    println!("Couldn't start the bot: {}", err);
+                    return;
+                }
+            },
+        });
+    }
+    let key_sets = if args.humans == [true, true] {
+        VERSUS_KEYS
+    } else {
+        [MOVE_KEYS; 2]
+    };
+
+    let window_width = versus_left(2, width) - MARGIN_LEFT;
+    set_window_size(
+        window_width as u32,
+        ((height + 3) as f32 * SQUARE_SIZE) as u32,
+    );
+    let mut game_state = GameState::Menu;
+
+    loop {
+        clear_background(Color::from_rgba(40, 40, 40, 255));
+        if let GameState::Menu = game_state {
+            draw_menu(width, height);
+            if is_key_pressed(KeyCode::Space) {
+                game_state = GameState::Playing;
+                versus.start(get_time());
+            }
+            next_frame().await;
+            continue;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+
+        if let GameState::Playing = game_state {
+            if versus
+                .sessions
+                .iter()
+                .any(|session| session.is_out_of_pieces())
+            {
+                println!("Out of pieces!");
+                break;
+            }
+            let moves = [0, 1].map(|side| {
+                if args.humans[side] {
+                    pressed_moves(&key_sets[side])
+                } else {
+                    Vec::new()
+                }
+            });
+            let bots = [0, 1].map(|side| players[side].as_ref().map(|bot| bot as &dyn Player));
+            versus.update(get_time(), [&moves[0], &moves[1]], bots);
+            if versus.is_over() {
+                game_state = GameState::GameOver;
+            }
+        }
+
+        for (side, session) in versus.sessions.iter().enumerate() {
+            let left = versus_left(side, width);
+            let (waiting, ready) = versus.queues[side].rows(get_time());
+            draw_tetris_grid(width, height, left);
+            draw_board(&session.game.board, left);
+            draw_garbage_meter(waiting, ready, height, left);
+            draw_score(session.game.board.score, width, left);
+            draw_next_pieces(&session.game.piece_chooser.next_pieces, width, left);
+            draw_hold(session.game.held, width, left);
+            let mut stats = versus.stats(side, get_time());
+            if let Some(bot) = &players[side] {
+                stats.extend(bot.stats());
+            }
+            draw_stats(&stats, width, left);
+        }
+
+        if let GameState::GameOver = game_state {
+            let title = match versus.winner() {
+                Some(side) => format!("Player {} wins!", side + 1),
+                None => "Draw!".to_string(),
+            };
+            draw_versus_over(&title, "Press space to restart", window_width, height);
+            if is_key_pressed(KeyCode::Space) {
+                versus.restart(get_time());
+                game_state = GameState::Playing;
+            }
+        }
+
+        // bots on their own play a move a frame, which is too quick to follow
+        if !args.humans.contains(&true) {
             sleep(std::time::Duration::from_millis(100));
         }
 
         next_frame().await
     }
+}
+
+// hosts or joins a network match, whichever was asked for
+fn connect(args: &Args) -> std::io::Result<(Connection, Hello)> {
+    if let Some(address) = &args.join {
+        return Connection::join(address);
+    }
+    let port = args.host.unwrap_or(DEFAULT_PORT);
+    let hello = Hello {
+        seed: args.seed.unwrap_or_else(::rand::random),
+        randomizer: args.randomizer,
+        width: args.width,
+        height: args.height,
+    };
+    let listener = Connection::listen(port)?;
+    println!("Waiting for someone to join on port {}...", port);
+    Ok((Connection::host(&listener, hello)?, hello))
+}
+
+async fn run_network(args: Args, connection: Connection, hello: Hello) {
+    let table = match &args.attack_table {
+        Some(path) => match AttackTable::load(path) {
+            Ok(table) => table,
+            Err(err) => {
+                println!("Couldn't load attack table: {}", err);
+                return;
+            }
+        },
+        None => AttackTable::default(),
+    };
+    let (width, height) = (hello.width, hello.height);
+    let mut net = NetMatch::new(connection, hello, table, get_time());
+    let player = match BotWorker::from_args(&args) {
+        Ok(player) => player,
+        Err(err) => {
+            println!("Couldn't start the bot: {}", err);
+            return;
+        }
+    };
+
+    let window_width = versus_left(2, width) - MARGIN_LEFT;
+    set_window_size(
+        window_width as u32,
+        ((height + 3) as f32 * SQUARE_SIZE) as u32,
+    );
+
+    loop {
+        clear_background(Color::from_rgba(40, 40, 40, 255));
+        if is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+
+        let moves = if args.manual {
+            pressed_moves(&MOVE_KEYS)
+        } else {
+            Vec::new()
+        };
+        let bot = (!args.manual).then_some(&player as &dyn Player);
+        net.update(get_time(), &moves, bot);
 
-    println!("Final score: {}", board.score);
+        // this side is always on the left
+        let sides = [net.side, 1 - net.side];
+        for (left_side, &side) in sides.iter().enumerate() {
+            let game = &net.versus.sessions[side].game;
+            let mut stats = net.stats(side);
+            if side == net.side {
+                stats.extend(player.stats());
+            }
+            let left = versus_left(left_side, width);
+            draw_tetris_grid(width, height, left);
+            draw_board(&game.board, left);
+            draw_score(game.board.score, width, left);
+            draw_next_pieces(&game.piece_chooser.next_pieces, width, left);
+            draw_hold(game.held, width, left);
+            draw_stats(&stats, width, left);
+        }
+        let (waiting, ready) = net.versus.queues[net.side].rows(net.time());
+        draw_garbage_meter(waiting, ready, height, versus_left(0, width));
+
+        if net.is_over() {
+            let (title, prompt) = match &net.error {
+                Some(err) => ("Match stopped", err.as_str()),
+                None if net.versus.winner() == Some(net.side) => {
+                    ("You win!", "Press escape to quit")
+                }
+                None if net.versus.winner().is_some() => ("You lose!", "Press escape to quit"),
+                None => ("It's a draw!", "Press escape to quit"),
+            };
+            draw_versus_over(title, prompt, window_width, height);
+        } else if net.waiting(get_time()) {
+            draw_versus_over(
+                "Waiting",
+                "for the other player's moves",
+                window_width,
+                height,
+            );
+        }
+
+        // the bot plays a move a frame, which is too quick to follow
+        if !args.manual {
+            sleep(std::time::Duration::from_millis(100));
+        }
+
+        next_frame().await
+    }
+
+    if let Some(err) = &net.error {
+        println!("{}", err);
+    }
+}
+
+async fn play_replay(replay: Replay) {
+    let (width, height) = (replay.ruleset.width, replay.ruleset.height);
+    let mut player = ReplayPlayer::new(replay);
+
+    loop {
+        set_board_window_size(width, height);
+        clear_background(Color::from_rgba(40, 40, 40, 255));
+
+        if is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+        if is_key_pressed(KeyCode::Space) {
+            player.paused = !player.paused;
+        }
+        if is_key_pressed(KeyCode::N) {
+            player.paused = true;
+            player.step();
+            let _ = notation::write_ascii(
+                &mut std::io::stdout(),
+                &player.game.board,
+                Some(&player.game.piece),
+            );
+        }
+        if is_key_pressed(KeyCode::Right) {
+            player.seek(player.time + 5.0);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            player.seek(player.time - 5.0);
+        }
+        if is_key_pressed(KeyCode::Up) {
+            player.speed = (player.speed * 2.0).min(32.0);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            player.speed = (player.speed / 2.0).max(0.125);
+        }
+        player.update(get_frame_time() as f64);
+
+        if player.game_over {
+            draw_game_over("Game over!", player.game.board.score, width, height);
+        } else {
+            draw_tetris_grid(width, height, MARGIN_LEFT);
+            draw_board(&player.game.board, MARGIN_LEFT);
+            draw_score(player.game.board.score, width, MARGIN_LEFT);
+            draw_next_pieces(&player.game.piece_chooser.next_pieces, width, MARGIN_LEFT);
+        }
+        draw_replay_status(
+            player.time,
+            player.replay.duration(),
+            player.speed,
+            player.paused,
+            player.desync.as_deref(),
+            height,
+        );
+
+        next_frame().await
+    }
 }
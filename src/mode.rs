@@ -0,0 +1,153 @@
+// the different ways to play, picked with --mode
+
+use crate::constants::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    // play until topping out or running out of pieces
+    Endless,
+    // clear `SPRINT_LINES` lines as fast as possible
+    Sprint,
+    // score as much as possible before the time runs out
+    Ultra { time_limit: f64 },
+    // clear `lines` lines while gravity speeds up every `LINES_PER_LEVEL`
+    Marathon { lines: usize },
+    // dig through `lines` rows of garbage as fast as possible
+    // `messiness` is the chance each row's hole moves away from the one below it
+    Dig { lines: usize, messiness: f64 },
+    // last as long as possible while garbage comes up faster and faster
+    Survival,
+    // two games side by side sending each other garbage (see versus.rs)
+    Versus,
+    // no gravity or topping out, and placements can be undone (see `Session::undo`)
+    Zen,
+    // reach a puzzle's goal with the pieces it gives (see puzzle.rs), picked with --puzzle
+    Puzzle,
+}
+
+impl Mode {
+    // a mode with its default settings
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "endless" => Some(Mode::Endless),
+            "sprint" => Some(Mode::Sprint),
+            "ultra" => Some(Mode::Ultra {
+                time_limit: ULTRA_TIME_LIMIT,
+            }),
+            "marathon" => Some(Mode::Marathon {
+                lines: MARATHON_LINES,
+            }),
+            "dig" => Some(Mode::Dig {
+                lines: DIG_LINES,
+                messiness: DIG_MESSINESS,
+            }),
+            "survival" => Some(Mode::Survival),
+            "zen" => Some(Mode::Zen),
+            "versus" => Some(Mode::Versus),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Endless => "endless",
+            Mode::Sprint => "sprint",
+            Mode::Ultra { .. } => "ultra",
+            Mode::Marathon { .. } => "marathon",
+            Mode::Dig { .. } => "dig",
+            Mode::Survival => "survival",
+            Mode::Versus => "versus",
+            Mode::Zen => "zen",
+            Mode::Puzzle => "puzzle",
+        }
+    }
+
+    // the name along with any settings, so records for different settings are kept apart
+    pub fn key(self) -> String {
+        match self {
+            Mode::Ultra { time_limit } => format!("ultra {}s", time_limit),
+            Mode::Marathon { lines } => format!("marathon {}", lines),
+            Mode::Dig { lines, messiness } => format!("dig {} {}", lines, messiness),
+            _ => self.name().to_string(),
+        }
+    }
+
+    // the mode a key was made from, settings and all
+    pub fn from_key(key: &str) -> Option<Mode> {
+        let mut parts = key.split(' ');
+        let name = parts.next()?;
+        let mode = match name {
+            "ultra" => Mode::Ultra {
+                time_limit: parts.next()?.strip_suffix('s')?.parse().ok()?,
+            },
+            "marathon" => Mode::Marathon {
+                lines: parts.next()?.parse().ok()?,
+            },
+            "dig" => Mode::Dig {
+                lines: parts.next()?.parse().ok()?,
+                messiness: parts.next()?.parse().ok()?,
+            },
+            _ => Mode::from_name(name)?,
+        };
+        match parts.next() {
+            Some(_) => None,
+            None => Some(mode),
+        }
+    }
+
+    // how many lines finish the game, if any
+    pub fn line_goal(self) -> Option<usize> {
+        match self {
+            Mode::Endless
+            | Mode::Ultra { .. }
+            | Mode::Dig { .. }
+            | Mode::Survival
+            | Mode::Versus
+            | Mode::Zen
+            | Mode::Puzzle => None,
+            Mode::Sprint => Some(SPRINT_LINES),
+            Mode::Marathon { lines } => Some(lines),
+        }
+    }
+
+    // how many seconds the game lasts, if it's cut off
+    pub fn time_limit(self) -> Option<f64> {
+        match self {
+            Mode::Ultra { time_limit } => Some(time_limit),
+            _ => None,
+        }
+    }
+
+    // the level after clearing `lines`, which only goes up in marathon
+    pub fn level(self, lines: usize) -> usize {
+        match self {
+            Mode::Marathon { lines: goal } => {
+                let max_level = (goal / LINES_PER_LEVEL).max(1);
+                (1 + lines / LINES_PER_LEVEL).min(max_level)
+            }
+            _ => 1,
+        }
+    }
+
+    // whether a personal best is a time rather than a score
+    pub fn is_timed(self) -> bool {
+        matches!(self, Mode::Sprint | Mode::Dig { .. } | Mode::Survival)
+    }
+
+    // whether a lower personal best is a better one
+    pub fn lower_is_better(self) -> bool {
+        matches!(self, Mode::Sprint | Mode::Dig { .. })
+    }
+}
+
+// seconds between each gravity tick at a level, following the guideline curve
+// (level 1 is `DROP_TIME`)
+pub fn drop_time(level: usize) -> f64 {
+    let level = level as f64 - 1.0;
+    DROP_TIME * (0.8 - level * 0.007).powf(level)
+}
+
+// seconds between garbage rows in survival mode once `sent` rows have come up
+pub fn survival_interval(sent: usize) -> f64 {
+    (SURVIVAL_INTERVAL * SURVIVAL_SPEEDUP.powi(sent as i32)).max(SURVIVAL_MIN_INTERVAL)
+}
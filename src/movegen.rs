@@ -0,0 +1,101 @@
+// finding everywhere a piece can be placed, and the moves that get it there, for bots
+//
+// only the moves a player has are used (so no kicks), and a placement is somewhere the
+// piece rests on something, reached by any mix of moves and then locked with a hard drop
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::game::*;
+
+#[derive(Clone)]
+pub struct Placement {
+    // where the piece ends up
+    pub piece: Piece,
+    // the moves from the game as it was, holding first if it's the held (or next) piece
+    pub moves: Vec<Move>,
+}
+
+impl Placement {
+    // the cells the piece covers, sorted so placements can be compared
+    pub fn cells(&self) -> [(isize, isize); 4] {
+        let mut cells = self.piece.get_coords();
+        cells.sort();
+        cells
+    }
+
+    // the board once the piece is locked there and any lines are cleared, and how many were
+    pub fn result(&self, game: &Game) -> (Board, usize) {
+        let mut board = game.board.clone();
+        board.remove_piece(&game.piece);
+        board.add_piece(&self.piece);
+        let lines = board.clear_lines();
+        (board, lines)
+    }
+}
+
+// every placement of the current piece and, if it can be held, of the one holding brings out
+// each one is reached with as few moves as possible, and pieces covering the same cells in
+// different orientations only come up once
+pub fn placements(game: &Game) -> Vec<Placement> {
+    let mut placements = search(game, Vec::new());
+    if game.can_hold {
+        let mut held = game.clone();
+        held.move_piece(Move::Hold);
+        if held.piece.tetromino != game.piece.tetromino {
+            placements.extend(search(&held, vec![Move::Hold]));
+        }
+    }
+    placements
+}
+
+// breadth first from where the piece is, so the first path found to anywhere is one of the
+// shortest
+fn search(game: &Game, moves: Vec<Move>) -> Vec<Placement> {
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    // moves a piece on `board`, which is otherwise kept without it
+    let mut step = |piece: &Piece, mov: Move| {
+        let mut piece = piece.clone();
+        board.add_piece(&piece);
+        board.move_piece(&mut piece, mov);
+        board.remove_piece(&piece);
+        piece
+    };
+
+    let key = |piece: &Piece| (piece.x, piece.y, piece.orientation as u8);
+    let mut seen = HashSet::from([key(&game.piece)]);
+    let mut covered = HashSet::new();
+    let mut placements = Vec::new();
+    let mut queue = VecDeque::from([(game.piece.clone(), moves)]);
+    while let Some((piece, moves)) = queue.pop_front() {
+        let below = step(&piece, Move::Down);
+        if below == piece {
+            // whatever moves down at the end can be a drop instead
+            let mut path = moves.clone();
+            while let Some(Move::Down) = path.last() {
+                path.pop();
+            }
+            path.push(Move::Drop);
+            let placement = Placement {
+                piece: piece.clone(),
+                moves: path,
+            };
+            if covered.insert(placement.cells()) {
+                placements.push(placement);
+            }
+        }
+        for mov in [Move::Left, Move::Right, Move::Rotate, Move::Down] {
+            let moved = if let Move::Down = mov {
+                below.clone()
+            } else {
+                step(&piece, mov)
+            };
+            if seen.insert(key(&moved)) {
+                let mut moves = moves.clone();
+                moves.push(mov);
+                queue.push_back((moved, moves));
+            }
+        }
+    }
+    placements
+}
@@ -0,0 +1,722 @@
+// versus over the network in lockstep, one player hosting and the other joining over TCP
+//
+// both sides play the whole match (both games and the garbage sent between them) on the
+// same seeded engine, a step of `FRAME_TIME` seconds at a time, and all that's sent back and
+// forth is each side's moves for every step
+// a step is only played once both sides' moves for it are in, so the two copies of the match
+// get the same moves on the same steps and come out the same
+//
+// moves go in `NET_INPUT_DELAY` steps after the one being played when they're made, which
+// gives them time to get to the other side: with a round trip shorter than that neither side
+// ever waits, and with a longer one the match holds still until the moves come in, then
+// catches up
+// a bot chooses a piece's moves from the match as far as it's been played, once the moves
+// it chose for the last piece have all gone in
+//
+// every `HASH_STEPS` steps the joining side sends a hash of both boards, and one that isn't
+// the same as the host's means a copy has gone out of sync: the host sends its whole match
+// over and both sides carry on from that
+//
+// the protocol is one message per line:
+//   tetris-rs net <version> <seed> <randomizer> <width> <height>
+//                           sent by the host when a player joins
+//   tetris-rs net <version>  the joining player's answer
+//   input <step> <moves>    the sender's moves for a step, like `input 12 Left Rotate`
+//                           (sent for every step, with no moves if there weren't any)
+//   hash <steps> <hash>     the joining side's hash of both boards after that many steps
+//   ping <time> / pong <time>  for measuring the round trip
+//   state <steps> <match>   the host's match after that many steps: for each side its combo,
+//                           back to back and rows sent, the garbage waiting for it as rows
+//                           and when they're ready, then its game in the save format (see
+//                           save.rs), all with `;` in place of line breaks
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::attack::{AttackTable, Attacker};
+use crate::constants::*;
+use crate::game::*;
+use crate::player::Player;
+use crate::replay::parse_move;
+use crate::save::SavedGame;
+use crate::versus::{GarbageQueue, Versus};
+
+// bump this whenever a message changes
+pub const NET_VERSION: u32 = 4;
+const NET_HEADER: &str = "tetris-rs net";
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    Input(u64, Vec<Move>),
+    Hash(u64, u64),
+    Ping(f64),
+    Pong(f64),
+    State(u64, String),
+}
+
+impl Message {
+    fn to_line(&self) -> String {
+        match self {
+            Message::Input(step, moves) => {
+                let mut line = format!("input {}", step);
+                for mov in moves {
+                    line += &format!(" {:?}", mov);
+                }
+                line
+            }
+            Message::Hash(steps, hash) => format!("hash {} {}", steps, hash),
+            Message::Ping(time) => format!("ping {}", time),
+            Message::Pong(time) => format!("pong {}", time),
+            Message::State(steps, text) => format!("state {} {}", steps, text.replace('\n', ";")),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Message> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "input" => {
+                let mut parts = rest.split(' ');
+                let step = parts.next()?.parse().ok()?;
+                let moves = parts.map(parse_move).collect::<Option<_>>()?;
+                Some(Message::Input(step, moves))
+            }
+            "hash" => {
+                let (steps, hash) = rest.split_once(' ')?;
+                Some(Message::Hash(steps.parse().ok()?, hash.parse().ok()?))
+            }
+            "ping" => rest.parse().ok().map(Message::Ping),
+            "pong" => rest.parse().ok().map(Message::Pong),
+            "state" => {
+                let (steps, text) = rest.split_once(' ')?;
+                Some(Message::State(steps.parse().ok()?, text.replace(';', "\n")))
+            }
+            _ => None,
+        }
+    }
+}
+
+// the game settings both sides have to agree on, picked by the host
+#[derive(Clone, Copy, Debug)]
+pub struct Hello {
+    pub seed: u64,
+    pub randomizer: Randomizer,
+    pub width: usize,
+    pub height: usize,
+}
+
+pub struct Connection {
+    stream: TcpStream,
+    // lines read by a background thread, so reading never holds up a frame
+    incoming: Receiver<io::Result<String>>,
+    // whether this side is the host, whose game is the first in the match
+    pub hosting: bool,
+}
+
+impl Connection {
+    // listens for a player to join on `port`, on every network this machine is on
+    pub fn listen(port: u16) -> io::Result<TcpListener> {
+        TcpListener::bind(("0.0.0.0", port))
+    }
+
+    // waits for someone to join and tells them what's being played
+    pub fn host(listener: &TcpListener, hello: Hello) -> io::Result<Connection> {
+        let (stream, _) = listener.accept()?;
+        let mut connection = Connection::new(stream, true)?;
+        connection.send_line(&format!(
+            "{} {} {} {} {} {}",
+            NET_HEADER,
+            NET_VERSION,
+            hello.seed,
+            hello.randomizer.name(),
+            hello.width,
+            hello.height
+        ))?;
+        let answer = connection.recv_line()?;
+        match parse_header(&answer)?.as_slice() {
+            [] => Ok(connection),
+            _ => Err(invalid_data("unexpected answer to hello")),
+        }
+    }
+
+    // connects to a host and finds out what's being played
+    pub fn join(address: &str) -> io::Result<(Connection, Hello)> {
+        let mut connection = Connection::new(TcpStream::connect(address)?, false)?;
+        let line = connection.recv_line()?;
+        let hello = match parse_header(&line)?.as_slice() {
+            [seed, randomizer, width, height] => Hello {
+                seed: seed.parse().map_err(|_| invalid_data("bad seed"))?,
+                randomizer: Randomizer::from_name(randomizer)
+                    .ok_or_else(|| invalid_data("bad randomizer"))?,
+                width: width.parse().map_err(|_| invalid_data("bad width"))?,
+                height: height.parse().map_err(|_| invalid_data("bad height"))?,
+            },
+            _ => return Err(invalid_data("bad hello")),
+        };
+        if hello.width < MIN_SIZE || hello.height < MIN_SIZE {
+            return Err(invalid_data("board is too small"));
+        }
+        connection.send_line(&format!("{} {}", NET_HEADER, NET_VERSION))?;
+        Ok((connection, hello))
+    }
+
+    fn new(stream: TcpStream, hosting: bool) -> io::Result<Connection> {
+        // moves are tiny and should go out straight away
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Connection {
+            stream,
+            incoming,
+            hosting,
+        })
+    }
+
+    fn send_line(&mut self, line: &str) -> io::Result<()> {
+        self.stream.write_all(format!("{}\n", line).as_bytes())
+    }
+
+    // blocks until a line comes in, only used while connecting
+    fn recv_line(&mut self) -> io::Result<String> {
+        self.incoming
+            .recv()
+            .unwrap_or_else(|_| Err(io::ErrorKind::UnexpectedEof.into()))
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        self.send_line(&message.to_line())
+    }
+
+    // every message that's come in since the last call
+    // the other side leaving is only reported once everything it sent has been handed over
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(line) => {
+                    let line = line?;
+                    let message = Message::parse(&line)
+                        .ok_or_else(|| invalid_data(&format!("bad message `{}`", line)))?;
+                    messages.push(message);
+                }
+                Err(TryRecvError::Empty) => return Ok(messages),
+                Err(TryRecvError::Disconnected) if messages.is_empty() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the other player left",
+                    ))
+                }
+                Err(TryRecvError::Disconnected) => return Ok(messages),
+            }
+        }
+    }
+}
+
+// checks the protocol name and version and returns whatever comes after them
+fn parse_header(line: &str) -> io::Result<Vec<&str>> {
+    let rest = line
+        .strip_prefix(NET_HEADER)
+        .ok_or_else(|| invalid_data("not a tetris-rs player"))?;
+    let mut parts = rest.split_whitespace();
+    let version = parts.next().and_then(|version| version.parse::<u32>().ok());
+    if version != Some(NET_VERSION) {
+        return Err(invalid_data(&format!(
+            "the other player is on a different version ({:?}, this is {})",
+            version, NET_VERSION
+        )));
+    }
+    Ok(parts.collect())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// FNV-1a over every cell and the score, the same on any machine or build
+pub fn board_hash(board: &Board) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let cells = board.grid.iter().flatten().map(|&cell| cell as u8);
+    for byte in cells.chain(board.score.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// how many steps back the moves played are kept, for playing them again after a resync
+// (the joining side can only be ahead of the host by the input delay)
+const KEPT_STEPS: u64 = 4 * NET_INPUT_DELAY;
+
+pub struct NetMatch {
+    pub connection: Connection,
+    // the whole match, played the same on both sides, and which of its sides this one is
+    // (the host's is the first)
+    pub versus: Versus,
+    pub side: usize,
+    // steps played so far, counted from when the match was set up on this side's clock
+    pub steps: u64,
+    start_time: f64,
+    // both sides' moves by step, from `KEPT_STEPS` before the next one to be played on, for
+    // as far as they've come in
+    inputs: BTreeMap<u64, [Option<Vec<Move>>; 2]>,
+    // this side's moves that haven't gone into a step yet, and the step they'll go in
+    unsent: Vec<Move>,
+    next_input: u64,
+    // the moves the bot chose that haven't been made yet, and the piece they were for
+    chosen_moves: Vec<Move>,
+    chosen_for: Option<usize>,
+    // the host's hashes and the joining side's that haven't been checked yet, by step
+    hashes: BTreeMap<u64, u64>,
+    their_hashes: BTreeMap<u64, u64>,
+    // hashes up to this step could have been made before the last resync, so aren't checked
+    synced: u64,
+    pub resyncs: usize,
+    // round trip time in seconds, once it's been measured
+    pub ping: Option<f64>,
+    // why the match was cut short, if it was
+    pub error: Option<String>,
+    last_ping: f64,
+}
+
+impl NetMatch {
+    pub fn new(connection: Connection, hello: Hello, table: AttackTable, time: f64) -> NetMatch {
+        let mut versus = Versus::new(
+            hello.seed,
+            hello.randomizer,
+            hello.width,
+            hello.height,
+            table,
+            0.0,
+        );
+        versus.start(0.0);
+        // nobody can have made a move for the steps before the first ones sent
+        let inputs = (0..NET_INPUT_DELAY)
+            .map(|step| (step, [Some(Vec::new()), Some(Vec::new())]))
+            .collect();
+        NetMatch {
+            side: if connection.hosting { 0 } else { 1 },
+            connection,
+            versus,
+            steps: 0,
+            start_time: time,
+            inputs,
+            unsent: Vec::new(),
+            next_input: NET_INPUT_DELAY,
+            chosen_moves: Vec::new(),
+            chosen_for: None,
+            hashes: BTreeMap::new(),
+            their_hashes: BTreeMap::new(),
+            synced: 0,
+            resyncs: 0,
+            ping: None,
+            error: None,
+            last_ping: time,
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.versus.is_over() || self.error.is_some()
+    }
+
+    // seconds into the match as far as it's been played
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * FRAME_TIME
+    }
+
+    // whether the match is behind the clock for want of the other side's moves
+    pub fn waiting(&self, time: f64) -> bool {
+        !self.is_over() && self.steps < self.steps_due(time) && self.step_moves().is_none()
+    }
+
+    fn steps_due(&self, time: f64) -> u64 {
+        ((time - self.start_time) / FRAME_TIME).max(0.0) as u64
+    }
+
+    // runs one frame: takes in the other side's messages and this side's moves, and plays
+    // the match up to the clock as far as both sides' moves are in
+    pub fn update(&mut self, time: f64, moves: &[Move], player: Option<&dyn Player>) {
+        if self.is_over() {
+            return;
+        }
+        if let Err(err) = self.exchange(time, moves, player) {
+            self.error = Some(err.to_string());
+        }
+    }
+
+    fn exchange(
+        &mut self,
+        time: f64,
+        moves: &[Move],
+        player: Option<&dyn Player>,
+    ) -> io::Result<()> {
+        for message in self.connection.receive()? {
+            match message {
+                Message::Input(step, moves) => {
+                    let them = 1 - self.side;
+                    if step >= self.steps {
+                        self.inputs.entry(step).or_default()[them] = Some(moves);
+                    }
+                }
+                Message::Hash(steps, hash) => {
+                    self.their_hashes.insert(steps, hash);
+                }
+                Message::Ping(sent) => self.connection.send(&Message::Pong(sent))?,
+                Message::Pong(sent) => self.ping = Some(time - sent),
+                Message::State(steps, text) => self.load_state(steps, &text)?,
+            }
+        }
+
+        self.unsent.extend_from_slice(moves);
+        if let Some(player) = player {
+            self.play_bot(player);
+        }
+
+        while self.steps < self.steps_due(time) && !self.versus.is_over() {
+            let Some(moves) = self.step_moves() else {
+                break;
+            };
+            self.play_step(moves);
+            self.send_inputs()?;
+            if self.steps.is_multiple_of(HASH_STEPS) {
+                let hash = self.match_hash();
+                if self.side == 0 {
+                    self.hashes.insert(self.steps, hash);
+                } else {
+                    self.connection.send(&Message::Hash(self.steps, hash))?;
+                }
+            }
+            self.check_hashes()?;
+        }
+
+        if time - self.last_ping >= PING_INTERVAL {
+            self.last_ping = time;
+            self.connection.send(&Message::Ping(time))?;
+        }
+        Ok(())
+    }
+
+    // the bot makes a move a frame, and chooses the next piece's moves once it's played all
+    // of the last one's
+    fn play_bot(&mut self, player: &dyn Player) {
+        if !self.chosen_moves.is_empty() {
+            self.unsent.push(self.chosen_moves.remove(0));
+            return;
+        }
+        let game = &self.versus.sessions[self.side].game;
+        if self.chosen_for != Some(game.num_tetrominos) {
+            self.chosen_moves = player.choose_moves(game);
+            if !self.chosen_moves.is_empty() {
+                self.chosen_for = Some(game.num_tetrominos);
+            }
+        }
+    }
+
+    // both sides' moves for the next step, if they're both in
+    fn step_moves(&self) -> Option<[Vec<Move>; 2]> {
+        match self.inputs.get(&self.steps)? {
+            [Some(first), Some(second)] => Some([first.clone(), second.clone()]),
+            _ => None,
+        }
+    }
+
+    fn play_step(&mut self, moves: [Vec<Move>; 2]) {
+        self.steps += 1;
+        let time = self.time();
+        self.versus
+            .update(time, [&moves[0], &moves[1]], [None, None]);
+        let kept = self.steps.saturating_sub(KEPT_STEPS);
+        self.inputs = self.inputs.split_off(&kept);
+    }
+
+    // sends this side's moves for the steps up to the input delay ahead of the match
+    fn send_inputs(&mut self) -> io::Result<()> {
+        while self.next_input < self.steps + NET_INPUT_DELAY {
+            let moves = std::mem::take(&mut self.unsent);
+            self.connection
+                .send(&Message::Input(self.next_input, moves.clone()))?;
+            self.inputs.entry(self.next_input).or_default()[self.side] = Some(moves);
+            self.next_input += 1;
+        }
+        Ok(())
+    }
+
+    fn match_hash(&self) -> u64 {
+        let [first, second] = &self.versus.sessions;
+        board_hash(&first.game.board) ^ board_hash(&second.game.board).rotate_left(1)
+    }
+
+    // the host compares each of the joining side's hashes with its own for the same step,
+    // and sends its match over if they're different
+    fn check_hashes(&mut self) -> io::Result<()> {
+        if self.side != 0 {
+            return Ok(());
+        }
+        let checked: Vec<u64> = self
+            .their_hashes
+            .keys()
+            .copied()
+            .filter(|steps| self.hashes.contains_key(steps))
+            .collect();
+        let mut in_sync = true;
+        for steps in checked {
+            let theirs = self.their_hashes.remove(&steps);
+            let ours = self.hashes.remove(&steps);
+            in_sync &= steps <= self.synced || theirs == ours;
+        }
+        if !in_sync {
+            let text = self.state_text();
+            self.connection
+                .send(&Message::State(self.steps, text.clone()))?;
+            // the host carries on from the same text, so both copies are exactly alike
+            self.load_state(self.steps, &text)?;
+        }
+        Ok(())
+    }
+
+    // the match as it is, for sending to the joining side when it's out of sync
+    fn state_text(&self) -> String {
+        let time = self.time();
+        let mut text = String::new();
+        for side in 0..2 {
+            let attacker = &self.versus.attackers[side];
+            text += &format!(
+                "attacker {} {} {}\n",
+                attacker.combo, attacker.back_to_back, attacker.sent
+            );
+            let queue: Vec<String> = self.versus.queues[side]
+                .attacks
+                .iter()
+                .map(|(rows, ready_time)| format!(" {} {}", rows, ready_time))
+                .collect();
+            text += &format!("queue{}\n", queue.concat());
+            text += &SavedGame::new(&self.versus.sessions[side], time).to_text();
+        }
+        text
+    }
+
+    // carries on from the host's match after `steps` steps, playing the steps since again
+    // if this side had got further
+    fn load_state(&mut self, steps: u64, text: &str) -> io::Result<()> {
+        let lines: Vec<&str> = text.lines().collect();
+        let starts: Vec<usize> = (0..lines.len())
+            .filter(|&i| lines[i].starts_with("attacker "))
+            .collect();
+        if starts.len() != 2 || starts[0] != 0 {
+            return Err(invalid_data("bad match state"));
+        }
+        let time = steps as f64 * FRAME_TIME;
+        for (side, &start) in starts.iter().enumerate() {
+            let end = starts.get(side + 1).copied().unwrap_or(lines.len());
+            let [attacker, queue, game @ ..] = &lines[start..end] else {
+                return Err(invalid_data("bad match state"));
+            };
+            self.versus.attackers[side] = parse_attacker(attacker)?;
+            self.versus.queues[side] = parse_queue(queue)?;
+            let saved = SavedGame::from_text(&game.join("\n"))?;
+            self.versus.sessions[side].resume(saved, false, time);
+        }
+
+        let played = self.steps;
+        self.steps = steps;
+        while self.steps < played {
+            let moves = self
+                .step_moves()
+                .ok_or_else(|| invalid_data("the match got too far ahead to be resynced"))?;
+            self.play_step(moves);
+        }
+        self.next_input = self.next_input.max(self.steps);
+        self.synced = steps + NET_INPUT_DELAY;
+        self.hashes.retain(|&step, _| step > self.synced);
+        self.resyncs += 1;
+        Ok(())
+    }
+
+    // what's shown next to each side's board
+    pub fn stats(&self, side: usize) -> Vec<String> {
+        let mut stats = self.versus.stats(side, self.time());
+        if side == self.side {
+            if let Some(ping) = self.ping {
+                stats.push(format!("Ping {}ms", (ping * 1000.0).round()));
+            }
+            if self.resyncs > 0 {
+                stats.push(format!("Resyncs {}", self.resyncs));
+            }
+        }
+        stats
+    }
+}
+
+fn parse_attacker(line: &str) -> io::Result<Attacker> {
+    let bad = || invalid_data("bad attacker in match state");
+    let parts: Vec<&str> = line.split(' ').collect();
+    let ["attacker", combo, back_to_back, sent] = parts.as_slice() else {
+        return Err(bad());
+    };
+    Ok(Attacker {
+        combo: combo.parse().map_err(|_| bad())?,
+        back_to_back: back_to_back.parse().map_err(|_| bad())?,
+        sent: sent.parse().map_err(|_| bad())?,
+    })
+}
+
+fn parse_queue(line: &str) -> io::Result<GarbageQueue> {
+    let bad = || invalid_data("bad garbage queue in match state");
+    let rest = line.strip_prefix("queue").ok_or_else(bad)?;
+    let parts: Vec<&str> = rest.split_whitespace().collect();
+    let mut queue = GarbageQueue::default();
+    for attack in parts.chunks(2) {
+        let [rows, ready_time] = attack else {
+            return Err(bad());
+        };
+        queue.push(
+            rows.parse().map_err(|_| bad())?,
+            ready_time.parse().map_err(|_| bad())?,
+        );
+    }
+    Ok(queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristic::HeuristicPlayer;
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Input(0, Vec::new()),
+            Message::Input(12, vec![Move::Left, Move::Rotate, Move::Hold, Move::Drop]),
+            Message::Hash(60, u64::MAX),
+            Message::Ping(12.5),
+            Message::Pong(12.5),
+            Message::State(
+                120,
+                "attacker 0 false 0\nqueue 2 1.5\ntetris-rs save".into(),
+            ),
+        ];
+        for message in messages {
+            let line = message.to_line();
+            assert!(!line.contains('\n'), "{}", line);
+            let parsed = Message::parse(&line).unwrap();
+            assert_eq!(parsed.to_line(), line);
+        }
+        assert!(Message::parse("input").is_none());
+        assert!(Message::parse("input 3 Jump").is_none());
+        assert!(Message::parse("hash 5").is_none());
+        assert!(Message::parse("state").is_none());
+        assert!(Message::parse("lost").is_none());
+    }
+
+    // a host and a joiner over loopback, on a port the system picks
+    fn connect(hello: Hello) -> (NetMatch, NetMatch) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || Connection::host(&listener, hello).unwrap());
+        let (joined, hello) = Connection::join(&address.to_string()).unwrap();
+        let hosted = host.join().unwrap();
+        (
+            NetMatch::new(hosted, hello, AttackTable::default(), 0.0),
+            NetMatch::new(joined, hello, AttackTable::default(), 0.0),
+        )
+    }
+
+    // plays both sides a frame at a time, and the clock only moves on once both have caught
+    // up with it, however long the moves take to come through
+    fn play_until(
+        host: &mut NetMatch,
+        joiner: &mut NetMatch,
+        bot: &dyn Player,
+        done: impl Fn(&NetMatch) -> bool,
+    ) {
+        let mut time = host.time();
+        while !done(host) {
+            assert!(host.time() < 120.0, "the bot stopped placing pieces");
+            time += FRAME_TIME;
+            host.update(time, &[], Some(bot));
+            joiner.update(time, &[], Some(bot));
+            while host.waiting(time) || joiner.waiting(time) {
+                thread::yield_now();
+                host.update(time, &[], None);
+                joiner.update(time, &[], None);
+            }
+            assert_eq!(host.error, None);
+            assert_eq!(joiner.error, None);
+        }
+    }
+
+    fn boards(net: &NetMatch) -> Vec<Vec<Vec<Tetromino>>> {
+        net.versus
+            .sessions
+            .iter()
+            .map(|session| session.game.board.grid.clone())
+            .collect()
+    }
+
+    #[test]
+    fn both_sides_play_the_same_match() {
+        let hello = Hello {
+            seed: 3,
+            randomizer: Randomizer::Random,
+            width: 10,
+            height: 20,
+        };
+        let (mut host, mut joiner) = connect(hello);
+        assert_eq!((host.side, joiner.side), (0, 1));
+        let bot = HeuristicPlayer::default();
+        play_until(&mut host, &mut joiner, &bot, |host| {
+            host.versus.sessions[1].game.num_tetrominos >= 10
+        });
+        // the joiner finishes off the steps the host has played
+        while joiner.steps < host.steps {
+            joiner.update(host.time() + 1.0, &[], None);
+            thread::yield_now();
+        }
+        let joiner_steps = joiner.steps;
+        while host.steps < joiner_steps {
+            host.update(joiner.time() + 1.0, &[], None);
+            thread::yield_now();
+        }
+        assert_eq!(host.steps, joiner.steps);
+        assert!(boards(&host) == boards(&joiner));
+        assert_eq!((host.resyncs, joiner.resyncs), (0, 0));
+    }
+
+    // one copy of the match is tampered with, which the next hash should catch and put right
+    #[test]
+    fn a_copy_out_of_sync_is_resynced() {
+        let hello = Hello {
+            seed: 9,
+            randomizer: Randomizer::Bag,
+            width: 10,
+            height: 20,
+        };
+        let (mut host, mut joiner) = connect(hello);
+        let floor = joiner.versus.sessions[0].game.board.height() - 1;
+        joiner.versus.sessions[0].game.board.grid[floor][0] = Tetromino::G;
+
+        let bot = HeuristicPlayer::default();
+        play_until(&mut host, &mut joiner, &bot, |host| host.resyncs > 0);
+        // the state the host sent is on its way, and the joiner takes it in on its next frame
+        while joiner.resyncs == 0 {
+            joiner.update(joiner.time(), &[], None);
+            thread::yield_now();
+        }
+        assert_eq!(joiner.error, None);
+        assert!(joiner.steps >= host.steps);
+        let joiner_steps = joiner.steps;
+        while host.steps < joiner_steps {
+            host.update(joiner.time() + 1.0, &[], None);
+            thread::yield_now();
+        }
+        assert!(boards(&host) == boards(&joiner));
+    }
+}
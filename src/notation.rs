@@ -0,0 +1,190 @@
+// plain text notation for boards, handy for tests and bug reports
+//
+// one line per row from the top down and one character per cell:
+//   I O T S Z J L   cells of that tetromino
+//   G               garbage
+//   . or _          empty
+//   i o t s z j l   the active piece (only one piece may be marked)
+//
+// rows cover the whole board including the hidden rows above the visible field
+// the board is as wide as the rows and as tall as them (`str::parse`) unless a height is
+// asked for, and boards with fewer rows than that (or than the smallest board) are filled
+// in with empty rows on top,
+// and the borders drawn by `write_ascii` are ignored so its output can be pasted back in
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::constants::*;
+use crate::game::*;
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.grid.iter() {
+            let row: String = row.iter().map(|cell| cell.to_char()).collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Board {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Board, String> {
+        parse_board(text, None).map(|(board, _)| board)
+    }
+}
+
+// parses a board along with the active piece, if one is marked in lowercase
+// the board has at least `height` visible rows if given, more if the text has more
+pub fn parse_board(text: &str, height: Option<usize>) -> Result<(Board, Option<Piece>), String> {
+    let rows: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim().trim_matches('|'))
+        .filter(|line| !line.is_empty() && !line.chars().all(|c| c == '+' || c == '-'))
+        .collect();
+    let width = rows
+        .first()
+        .map_or(DEFAULT_WIDTH, |row| row.chars().count());
+    if width < MIN_SIZE {
+        return Err(format!("expected at least {} cells in a row", MIN_SIZE));
+    }
+
+    let visible_rows = rows.len().saturating_sub(HIDDEN_ROWS);
+    let mut board = Board::new(width, visible_rows.max(height.unwrap_or(MIN_SIZE)));
+    let mut active_tetromino = None;
+    let mut active_cells = Vec::new();
+    let top = board.height() - rows.len();
+    for (i, row) in rows.iter().enumerate() {
+        let y = top + i;
+        if row.chars().count() != width {
+            return Err(format!(
+                "expected {} cells in row {} but got {}",
+                width,
+                i + 1,
+                row.chars().count()
+            ));
+        }
+        for (x, c) in row.chars().enumerate() {
+            let cell = match c {
+                '_' => Tetromino::E,
+                c if c.is_ascii_lowercase() => {
+                    let tetromino = Tetromino::from_char(c.to_ascii_uppercase())
+                        .filter(|tetromino| !matches!(tetromino, Tetromino::E | Tetromino::G))
+                        .ok_or_else(|| format!("unknown cell `{}` in row {}", c, i + 1))?;
+                    if active_tetromino.is_some_and(|active| active != tetromino) {
+                        return Err("more than one active piece is marked".to_string());
+                    }
+                    active_tetromino = Some(tetromino);
+                    active_cells.push((x as isize, y as isize));
+                    tetromino
+                }
+                c => Tetromino::from_char(c)
+                    .ok_or_else(|| format!("unknown cell `{}` in row {}", c, i + 1))?,
+            };
+            board.grid[y][x] = cell;
+        }
+    }
+
+    let piece = match active_tetromino {
+        Some(tetromino) => {
+            let cells: [(isize, isize); 4] = active_cells
+                .try_into()
+                .map_err(|_| "the active piece must have exactly 4 cells".to_string())?;
+            Some(
+                Piece::from_cells(tetromino, cells)
+                    .ok_or_else(|| "the active piece isn't a valid shape".to_string())?,
+            )
+        }
+        None => None,
+    };
+    Ok((board, piece))
+}
+
+// draws the board with walls and a floor, the active piece is written in lowercase
+pub fn write_ascii(
+    out: &mut impl io::Write,
+    board: &Board,
+    piece: Option<&Piece>,
+) -> io::Result<()> {
+    let active_cells = piece.map(|piece| piece.get_coords());
+    for (y, row) in board.grid.iter().enumerate() {
+        let row: String = row
+            .iter()
+            .enumerate()
+            .map(|(x, cell)| {
+                let is_active =
+                    active_cells.is_some_and(|cells| cells.contains(&(x as isize, y as isize)));
+                if is_active {
+                    cell.to_char().to_ascii_lowercase()
+                } else {
+                    cell.to_char()
+                }
+            })
+            .collect();
+        writeln!(out, "|{}|", row)?;
+    }
+    writeln!(out, "+{}+", "-".repeat(board.width()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_board(height: usize) -> (Board, Piece) {
+        let mut board = Board::new(10, height);
+        let floor = board.height() - 1;
+        for x in 1..10 {
+            board.grid[floor][x] = Tetromino::G;
+        }
+        board.grid[floor - 1][0] = Tetromino::J;
+        board.grid[floor - 1][1] = Tetromino::S;
+        let piece = board.spawn_piece(Tetromino::T).unwrap();
+        board.add_piece(&piece);
+        (board, piece)
+    }
+
+    #[test]
+    fn display_round_trips_at_any_height() {
+        for height in [MIN_SIZE, 10, DEFAULT_HEIGHT, 30] {
+            let (board, _) = sample_board(height);
+            let (parsed, piece) = parse_board(&board.to_string(), Some(height)).unwrap();
+            assert!(parsed.grid == board.grid, "height {}", height);
+            assert!(piece.is_none());
+            // and without being told the height
+            let parsed: Board = board.to_string().parse().unwrap();
+            assert!(parsed.grid == board.grid, "height {}", height);
+        }
+    }
+
+    #[test]
+    fn ascii_round_trips_with_the_active_piece() {
+        for height in [MIN_SIZE, 10, DEFAULT_HEIGHT] {
+            let (board, piece) = sample_board(height);
+            let mut out = Vec::new();
+            write_ascii(&mut out, &board, Some(&piece)).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            let (parsed, parsed_piece) = parse_board(&text, None).unwrap();
+            assert!(parsed.grid == board.grid, "height {}", height);
+            assert!(parsed_piece == Some(piece));
+        }
+    }
+
+    #[test]
+    fn short_boards_are_filled_in_on_top() {
+        let (board, _) = parse_board("GGGG.GGGGG\nGGGG.GGGGG", Some(DEFAULT_HEIGHT)).unwrap();
+        assert_eq!(board.visible_height(), DEFAULT_HEIGHT);
+        assert_eq!(board.grid[board.height() - 1][3], Tetromino::G);
+        assert_eq!(board.grid[board.height() - 1][4], Tetromino::E);
+        assert!(board.grid[..board.height() - 2]
+            .iter()
+            .flatten()
+            .all(|&cell| cell == Tetromino::E));
+        let board: Board = "GGGG.GGGGG\nGGGG.GGGGG".parse().unwrap();
+        assert_eq!(board.visible_height(), MIN_SIZE);
+        assert!("GG\nGG".parse::<Board>().is_err());
+        assert!("GGGG.GGGGG\nGGGG.GGGG".parse::<Board>().is_err());
+    }
+}
@@ -0,0 +1,596 @@
+// openers: set ways of placing the first bag or two of a game, ending in a T slot (or set up
+// for a perfect clear) that gets a good clear out of the first few pieces
+//
+// each bag of an opener is drawn as a diagram, one row per line from the top down separated
+// by `/`, standing on the bottom of the board:
+//   I O T S Z J L   where that bag's pieces go
+//   X               cells earlier bags left there
+//   .               empty
+// a bag can have a few diagrams to pick from, since which can be built depends on the order
+// its pieces come in, and every opener can be played mirrored too
+//
+// an opener is only started when the pieces that can be seen fit it, and it's given up on
+// as soon as one comes in an order it can't take (with one hold to juggle them), which with
+// the placements being exact happens in a fair few games
+//
+// with no kicks (see movegen.rs) a T can only be dropped into its slot pointing down, so the
+// T slots are this game's take on the real openers, with a double where a spin would go and
+// nothing credited as a T-spin
+
+use std::cell::{Cell, RefCell};
+
+use crate::constants::*;
+use crate::game::*;
+use crate::heuristic::HeuristicPlayer;
+use crate::movegen::{placements, Placement};
+use crate::pc;
+use crate::player::{Player, Thoughts};
+
+pub struct Opener {
+    pub name: &'static str,
+    // the diagrams for each bag, in the order they're built
+    pub bags: &'static [&'static [&'static str]],
+    // whether the opener is finished off with a perfect clear, which the T is held for
+    pub perfect_clear: bool,
+}
+
+pub const OPENERS: [Opener; 4] = [
+    Opener {
+        name: "TKI",
+        bags: &[&[
+            ".....JZZ../L....JSZZ./LTTTJJSSOO/LLTIIIISOO",
+            "......ZZ.I/L.....JZZI/LTTTSSJOOI/LLTSSJJOOI",
+        ]],
+        perfect_clear: false,
+    },
+    Opener {
+        name: "MKO",
+        bags: &[&[
+            "IS.....Z../ISS...ZZ../IJSTTTZLOO/IJJJTLLLOO",
+            "..Z......./.ZZ....J../.ZL....JS./OOLTTTJJSS/OOLLTIIIIS",
+        ]],
+        perfect_clear: false,
+    },
+    Opener {
+        name: "DT cannon",
+        bags: &[
+            &["S.....Z..I/SS...ZZ..I/JSTTTZLOOI/JJJTLLLOOI"],
+            &[
+                "......LJJJ/....LLLZZJ/....IIIIZZ/XTTTSSXOOX/XXTSSXXOOX",
+                ".SS....J../SSL....JJJ/LLL...IIII/XZZTTTXOOX/XXZZTXXOOX",
+            ],
+        ],
+        perfect_clear: false,
+    },
+    Opener {
+        name: "PCO",
+        bags: &[&["....SIIIIZ/....SSOOZZ/....JSOOZL/....JJJLLL"]],
+        perfect_clear: true,
+    },
+];
+
+// the opener going by this name, ignoring case and with `-` for spaces (so `dt-cannon`)
+pub fn find(name: &str) -> Option<&'static Opener> {
+    OPENERS
+        .iter()
+        .find(|opener| opener.name.replace(' ', "-").eq_ignore_ascii_case(name))
+}
+
+// a piece of an opener, with the cells it goes in as columns and rows from the bottom up
+#[derive(Clone, Copy)]
+struct Target {
+    tetromino: Tetromino,
+    cells: [(usize, usize); 4],
+}
+
+impl Target {
+    // the cells on the board, sorted like `Placement::cells`
+    fn board_cells(&self, board: &Board) -> [(isize, isize); 4] {
+        let mut cells = self
+            .cells
+            .map(|(x, row)| (x as isize, (board.height() - 1 - row) as isize));
+        cells.sort();
+        cells
+    }
+}
+
+// one way of building a bag, from its diagram
+struct Shape {
+    width: usize,
+    // cells that should be filled before the bag is started
+    filled: Vec<(usize, usize)>,
+    targets: Vec<Target>,
+}
+
+impl Shape {
+    fn parse(diagram: &str, mirrored: bool) -> Shape {
+        let rows: Vec<&str> = diagram.split('/').collect();
+        let width = rows[0].len();
+        let mut filled = Vec::new();
+        let mut targets: Vec<(Tetromino, Vec<(usize, usize)>)> = Vec::new();
+        for (i, text) in rows.iter().enumerate() {
+            let row = rows.len() - 1 - i;
+            for (x, c) in text.chars().enumerate() {
+                let (x, c) = match (mirrored, c) {
+                    (false, _) => (x, c),
+                    (true, 'L') => (width - 1 - x, 'J'),
+                    (true, 'J') => (width - 1 - x, 'L'),
+                    (true, 'S') => (width - 1 - x, 'Z'),
+                    (true, 'Z') => (width - 1 - x, 'S'),
+                    (true, _) => (width - 1 - x, c),
+                };
+                match c {
+                    '.' => (),
+                    'X' => filled.push((x, row)),
+                    _ => {
+                        let tetromino = Tetromino::from_char(c).unwrap();
+                        match targets.iter_mut().find(|(t, _)| *t == tetromino) {
+                            Some((_, cells)) => cells.push((x, row)),
+                            None => targets.push((tetromino, vec![(x, row)])),
+                        }
+                    }
+                }
+            }
+        }
+        Shape {
+            width,
+            filled,
+            // the diagrams above all have four cells for each piece
+            targets: targets
+                .into_iter()
+                .map(|(tetromino, cells)| Target {
+                    tetromino,
+                    cells: cells.try_into().unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    // whether the board has exactly the cells filled that the shape is built on
+    fn matches(&self, board: &Board) -> bool {
+        board.width() == self.width
+            && board.grid.iter().enumerate().all(|(y, row)| {
+                row.iter().enumerate().all(|(x, &cell)| {
+                    let filled = self.filled.contains(&(x, board.height() - 1 - y));
+                    (cell != Tetromino::E) == filled
+                })
+            })
+    }
+}
+
+// whether nothing's been placed in or above a target, which would keep it from ever being
+// filled
+fn open(board: &Board, target: &Target) -> bool {
+    let cells = target.board_cells(board);
+    cells.iter().all(|&(x, y)| {
+        (0..=y).all(|above| {
+            cells.contains(&(x, above)) || board.grid[above as usize][x as usize] == Tetromino::E
+        })
+    })
+}
+
+// whether a piece can be dropped straight into a target: it's open and has something to rest on
+fn placeable(board: &Board, target: &Target) -> bool {
+    let cells = target.board_cells(board);
+    let resting = cells.iter().any(|&(x, y)| {
+        y + 1 == board.height() as isize
+            || (!cells.contains(&(x, y + 1))
+                && board.grid[y as usize + 1][x as usize] != Tetromino::E)
+    });
+    open(board, target) && resting
+}
+
+// the board once the `i`th target is filled and any lines are cleared, with the targets left
+// moved down to match
+fn place(board: &Board, targets: &[Target], i: usize) -> (Board, Vec<Target>) {
+    let mut board = board.clone();
+    for (x, y) in targets[i].board_cells(&board) {
+        board.grid[y as usize][x as usize] = targets[i].tetromino;
+    }
+    let full: Vec<usize> = (0..board.height())
+        .filter(|&row| {
+            board.grid[board.height() - 1 - row]
+                .iter()
+                .all(|&cell| cell != Tetromino::E)
+        })
+        .collect();
+    board.clear_lines();
+    let mut left = targets.to_vec();
+    left.remove(i);
+    for target in &mut left {
+        for (_, row) in &mut target.cells {
+            *row -= full.iter().filter(|&&full| full < *row).count();
+        }
+    }
+    (board, left)
+}
+
+// whether the targets can all be filled by the pieces known about, holding where it helps
+// pieces nobody knows about yet are hoped to come in an order that works
+fn fits(
+    board: &Board,
+    targets: &[Target],
+    current: Option<Tetromino>,
+    held: Option<Tetromino>,
+    queue: &[Tetromino],
+) -> bool {
+    if !targets.iter().all(|target| open(board, target)) {
+        return false;
+    }
+    let Some(current) = current else {
+        return true;
+    };
+    if targets.is_empty() {
+        return true;
+    }
+    let next = queue.first().copied();
+    let rest = queue.get(1..).unwrap_or(&[]);
+    let fill = |piece: Tetromino| {
+        targets
+            .iter()
+            .position(|target| target.tetromino == piece && placeable(board, target))
+    };
+
+    if let Some(i) = fill(current) {
+        let (board, targets) = place(board, targets, i);
+        if fits(&board, &targets, next, held, rest) {
+            return true;
+        }
+    }
+    // holding brings out the held piece, or the next one if nothing's held yet
+    match held {
+        Some(held) => fill(held).is_some_and(|i| {
+            let (board, targets) = place(board, targets, i);
+            fits(&board, &targets, next, Some(current), rest)
+        }),
+        None => {
+            let Some(next) = next else {
+                return true;
+            };
+            fill(next).is_some_and(|i| {
+                let (board, targets) = place(board, targets, i);
+                let after = queue.get(1).copied();
+                fits(
+                    &board,
+                    &targets,
+                    after,
+                    Some(current),
+                    queue.get(2..).unwrap_or(&[]),
+                )
+            })
+        }
+    }
+}
+
+// the game's board without the falling piece, and the pieces coming up in order
+fn known(game: &Game) -> (Board, Vec<Tetromino>) {
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    let queue = game
+        .piece_chooser
+        .next_pieces
+        .iter()
+        .rev()
+        .copied()
+        .collect();
+    (board, queue)
+}
+
+// how far a game has got through an opener
+#[derive(Clone)]
+pub struct Plan {
+    pub opener: &'static Opener,
+    pub mirrored: bool,
+    // the bag being built and the pieces of it still to place
+    bag: usize,
+    targets: Vec<Target>,
+    // the board as it should be with those pieces still to place
+    expected: Board,
+}
+
+impl Plan {
+    // starts building an opener, if the game's on an empty board and the pieces it can see
+    // fit one of the ways of building the first bag
+    pub fn start(opener: &'static Opener, mirrored: bool, game: &Game) -> Option<Plan> {
+        let (board, _) = known(game);
+        let mut plan = Plan {
+            opener,
+            mirrored,
+            bag: 0,
+            targets: Vec::new(),
+            expected: board,
+        };
+        plan.pick_shape(game).then_some(plan)
+    }
+
+    // the opener's name, and whether it's mirrored
+    pub fn name(&self) -> String {
+        if self.mirrored {
+            format!("{} (mirrored)", self.opener.name)
+        } else {
+            self.opener.name.to_string()
+        }
+    }
+
+    // whether every bag has been built
+    pub fn done(&self) -> bool {
+        self.bag == self.opener.bags.len()
+    }
+
+    // picks the first of the bag's diagrams that's built on the board as it is and fits the
+    // pieces known about
+    fn pick_shape(&mut self, game: &Game) -> bool {
+        let (board, queue) = known(game);
+        for diagram in self.opener.bags[self.bag] {
+            let shape = Shape::parse(diagram, self.mirrored);
+            let current = Some(game.piece.tetromino);
+            if shape.matches(&board) && fits(&board, &shape.targets, current, game.held, &queue) {
+                self.targets = shape.targets;
+                self.expected = board;
+                return true;
+            }
+        }
+        false
+    }
+
+    // catches up with the pieces placed since, returning false if one of them went somewhere
+    // that isn't part of the opener (or the next bag can't be built)
+    pub fn update(&mut self, game: &Game) -> bool {
+        if self.done() {
+            return true;
+        }
+        let (board, _) = known(game);
+        let same = |other: &Board| {
+            board
+                .grid
+                .iter()
+                .flatten()
+                .zip(other.grid.iter().flatten())
+                .all(|(&a, &b)| (a == Tetromino::E) == (b == Tetromino::E))
+        };
+        if !same(&self.expected) {
+            let placed = (0..self.targets.len()).find_map(|i| {
+                let result = place(&self.expected, &self.targets, i);
+                (placeable(&self.expected, &self.targets[i]) && same(&result.0)).then_some(result)
+            });
+            let Some((expected, targets)) = placed else {
+                return false;
+            };
+            self.expected = expected;
+            self.targets = targets;
+        }
+        if self.targets.is_empty() {
+            self.bag += 1;
+            if !self.done() {
+                return self.pick_shape(game);
+            }
+        }
+        true
+    }
+
+    // the placement of the falling (or held) piece that keeps the opener going, if there is one
+    pub fn next(&self, game: &Game) -> Option<Placement> {
+        let (board, queue) = known(game);
+        placements(game).into_iter().find(|placement| {
+            let cells = placement.cells();
+            let Some(i) = self.targets.iter().position(|target| {
+                target.tetromino == placement.piece.tetromino && target.board_cells(&board) == cells
+            }) else {
+                return false;
+            };
+            let (board, targets) = place(&board, &self.targets, i);
+            // what's falling and held once the piece is placed
+            let holds = matches!(placement.moves.first(), Some(Move::Hold));
+            let (taken, held) = match (holds, game.held) {
+                (false, held) => (0, held),
+                (true, Some(_)) => (0, Some(game.piece.tetromino)),
+                (true, None) => (1, Some(game.piece.tetromino)),
+            };
+            let queue = queue.get(taken..).unwrap_or(&[]);
+            let rest = queue.get(1..).unwrap_or(&[]);
+            fits(&board, &targets, queue.first().copied(), held, rest)
+        })
+    }
+}
+
+// a plan for `opener` (either way round) or for whichever fits if it's None, picked by the
+// game's seed when a few do
+pub fn detect(opener: Option<&'static Opener>, game: &Game) -> Option<Plan> {
+    let mut plans: Vec<Plan> = OPENERS
+        .iter()
+        .filter(|candidate| opener.is_none_or(|opener| opener.name == candidate.name))
+        .flat_map(|candidate| [false, true].map(|mirrored| Plan::start(candidate, mirrored, game)))
+        .flatten()
+        .collect();
+    if plans.is_empty() {
+        return None;
+    }
+    let pick = game.piece_chooser.seed() as usize % plans.len();
+    Some(plans.swap_remove(pick))
+}
+
+// a bot that starts each game with an opener, then plays like the heuristic bot (going for
+// the perfect clear first, after one that sets it up)
+pub struct OpenerPlayer {
+    // the opener to play, or None for whichever fits the first pieces
+    opener: Option<&'static Opener>,
+    plan: RefCell<Option<Plan>>,
+    // whether it's after the perfect clear an opener set up
+    finishing: Cell<bool>,
+    heuristic: HeuristicPlayer,
+}
+
+impl OpenerPlayer {
+    pub fn new(opener: Option<&'static Opener>) -> OpenerPlayer {
+        OpenerPlayer {
+            opener,
+            plan: RefCell::new(None),
+            finishing: Cell::new(false),
+            heuristic: HeuristicPlayer::default(),
+        }
+    }
+}
+
+impl Player for OpenerPlayer {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        let mut plan = self.plan.borrow_mut();
+        if game.num_tetrominos == 0 {
+            *plan = detect(self.opener, game);
+            self.finishing.set(false);
+        }
+        if let Some(current) = plan.as_mut() {
+            if current.update(game) {
+                if let Some(placement) = current.next(game) {
+                    return placement.moves;
+                }
+                self.finishing
+                    .set(current.done() && current.opener.perfect_clear);
+            }
+            *plan = None;
+        }
+        if self.finishing.get() {
+            match pc::first_moves(game, PC_LINES) {
+                Some(moves) => return moves,
+                None => self.finishing.set(false),
+            }
+        }
+        self.heuristic.choose_moves(game)
+    }
+
+    fn thoughts(&self) -> Option<Thoughts> {
+        self.heuristic.thoughts()
+    }
+}
+
+// shows someone playing by hand where the next piece of an opener goes
+pub struct Trainer {
+    opener: Option<&'static Opener>,
+    // the plan as it was when each piece came in, so undoing takes it back too
+    plans: Vec<Option<Plan>>,
+    // the game the hint was last worked out for (its seed, pieces placed, falling and held
+    // pieces), and the hint
+    shown: Option<(u64, usize, Tetromino, Option<Tetromino>)>,
+    hint: Option<Piece>,
+}
+
+impl Trainer {
+    pub fn new(opener: Option<&'static Opener>) -> Trainer {
+        Trainer {
+            opener,
+            plans: Vec::new(),
+            shown: None,
+            hint: None,
+        }
+    }
+
+    // where the opener has the falling piece go, or the held one if it should be held
+    pub fn hint(&mut self, game: &Game) -> Option<Piece> {
+        let key = (
+            game.piece_chooser.seed(),
+            game.num_tetrominos,
+            game.piece.tetromino,
+            game.held,
+        );
+        if self.shown == Some(key) {
+            return self.hint.clone();
+        }
+
+        let pieces = game.num_tetrominos;
+        if pieces == 0 {
+            self.plans = vec![detect(self.opener, game)];
+        }
+        self.plans.truncate(pieces + 1);
+        while self.plans.len() <= pieces {
+            let mut plan = self.plans.last().cloned().flatten();
+            if plan.as_mut().is_some_and(|plan| !plan.update(game)) {
+                plan = None;
+            }
+            self.plans.push(plan);
+        }
+        let hint = self
+            .plans
+            .last()
+            .cloned()
+            .flatten()
+            .and_then(|plan| plan.next(game).map(|placement| placement.piece));
+        self.shown = Some(key);
+        self.hint = hint.clone();
+        hint
+    }
+
+    // how the opener's going, for the stats
+    pub fn status(&self) -> String {
+        match self.plans.last() {
+            Some(Some(plan)) if plan.done() => format!("{} done", plan.name()),
+            Some(Some(plan)) => plan.name(),
+            _ => "No opener".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the board once every piece of a bag's shape is in and the full lines are cleared
+    fn built(board: &Board, shape: &Shape) -> Board {
+        let mut board = board.clone();
+        for target in &shape.targets {
+            for (x, y) in target.board_cells(&board) {
+                board.grid[y as usize][x as usize] = target.tetromino;
+            }
+        }
+        board.clear_lines();
+        board
+    }
+
+    #[test]
+    fn every_diagram_parses_into_whole_pieces() {
+        for opener in &OPENERS {
+            for mirrored in [false, true] {
+                // every way the bags before this one could have been built
+                let mut boards = vec![Board::new(10, DEFAULT_HEIGHT)];
+                for (bag, diagrams) in opener.bags.iter().enumerate() {
+                    let mut next = Vec::new();
+                    for diagram in diagrams.iter() {
+                        let what = format!("{} bag {} `{}`", opener.name, bag + 1, diagram);
+                        let shape = Shape::parse(diagram, mirrored);
+                        assert_eq!(shape.width, 10, "{}", what);
+                        assert!(
+                            diagram.split('/').all(|row| row.len() == shape.width),
+                            "{}",
+                            what
+                        );
+
+                        let mut pieces: Vec<Tetromino> = shape
+                            .targets
+                            .iter()
+                            .map(|target| target.tetromino)
+                            .collect();
+                        pieces.sort_by_key(|&piece| piece as usize);
+                        pieces.dedup();
+                        let whole_bag = if opener.perfect_clear { 6 } else { 7 };
+                        assert_eq!(pieces.len(), shape.targets.len(), "{}", what);
+                        assert_eq!(pieces.len(), whole_bag, "{}", what);
+                        for target in &shape.targets {
+                            let cells = target.cells.map(|(x, row)| (x as isize, -(row as isize)));
+                            assert!(
+                                Piece::from_cells(target.tetromino, cells).is_some(),
+                                "{} has a {:?} that isn't one",
+                                what,
+                                target.tetromino
+                            );
+                        }
+
+                        // it's drawn on what an earlier bag left
+                        let before: Vec<&Board> =
+                            boards.iter().filter(|board| shape.matches(board)).collect();
+                        assert!(!before.is_empty(), "{} doesn't follow its last bag", what);
+                        next.extend(before.into_iter().map(|board| built(board, &shape)));
+                    }
+                    boards = next;
+                }
+            }
+        }
+    }
+}
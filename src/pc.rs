@@ -0,0 +1,449 @@
+// finding perfect clears: every way to place the pieces that are known about so the board
+// ends up empty, without stacking higher than a number of lines (4 is the usual)
+//
+// the pieces are placed with moves a player has (see movegen.rs), holding when it helps,
+// and only pieces that are known about are used: the current and held ones and the queue
+// the search gives up on a board early when
+//   something is above the lines the perfect clear has to fit in
+//   there aren't enough pieces left to fill those lines
+//   a full column walls off a part of the lines that can't be filled with whole pieces
+// and a board that's already been searched from (with the same pieces to come) isn't searched
+// again
+// only one solution is kept for each order the pieces are placed in (the first found, so the
+// one in the fewest lines), since that's what a player has to follow, and a way of filling
+// the lines that's already been found in one order isn't found again in another
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+
+use crate::args::Args;
+use crate::constants::*;
+use crate::fumen::{self, FumenPage};
+use crate::game::*;
+use crate::heuristic::HeuristicPlayer;
+use crate::movegen::{placements, Placement};
+use crate::player::{Player, Thoughts};
+use crate::session::Session;
+
+// prints every perfect clear from the board the game would start on
+pub fn print_solutions(args: &Args, lines: usize) -> Result<(), String> {
+    let session = Session::from_args(args, 0.0)?;
+    let game = &session.game;
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    // all of a puzzle's pieces are known from the start
+    let queue: Vec<Tetromino> = match &session.puzzle {
+        Some(puzzle) => puzzle.pieces[1..].to_vec(),
+        None => game
+            .piece_chooser
+            .next_pieces
+            .iter()
+            .rev()
+            .copied()
+            .collect(),
+    };
+    let queue_text: String = queue.iter().map(|piece| piece.to_char()).collect();
+    println!(
+        "Looking for perfect clears in {} lines with {} then {}",
+        lines,
+        game.piece.tetromino.to_char(),
+        queue_text
+    );
+
+    let solutions = solve(&board, &game.piece, game.held, &queue, lines);
+    for (i, solution) in solutions.iter().enumerate() {
+        println!("\nSolution {} ({})", i + 1, solution.order());
+        print!("{}", solution.text());
+        println!("{}", solution.fumen());
+    }
+    match solutions.len() {
+        0 => println!("No perfect clears"),
+        1 => println!("\n1 perfect clear"),
+        count => println!("\n{} perfect clears", count),
+    }
+    Ok(())
+}
+
+// one placement in a solution
+#[derive(Clone)]
+pub struct Step {
+    // the board before the piece is placed
+    pub board: Board,
+    pub placement: Placement,
+}
+
+#[derive(Clone)]
+pub struct Solution {
+    pub steps: Vec<Step>,
+    // the lines the perfect clear fits in, from the top down, with each piece where it went
+    // before any lines were cleared (cells that were there to start with are garbage)
+    pub shape: Vec<Vec<Tetromino>>,
+    // the cells of the shape each piece went in, in the order they were placed
+    pub pieces: Vec<PlacedPiece>,
+}
+
+// a piece and the cells of a solution's shape it went in
+pub type PlacedPiece = (Tetromino, [(usize, usize); 4]);
+
+impl Solution {
+    // the pieces in the order they're placed, like `TILJ`
+    pub fn order(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| step.placement.piece.tetromino.to_char())
+            .collect()
+    }
+
+    // a page for each placement, locking it in before the next
+    pub fn fumen(&self) -> String {
+        let pages: Vec<FumenPage> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| FumenPage {
+                board: step.board.clone(),
+                piece: Some(step.placement.piece.clone()),
+                comment: if i == 0 {
+                    format!("Perfect clear with {}", self.order())
+                } else {
+                    String::new()
+                },
+                lock: true,
+            })
+            .collect();
+        fumen::encode(&pages)
+    }
+
+    // the shape in the text notation (see notation.rs)
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for row in &self.shape {
+            let row: String = row.iter().map(|cell| cell.to_char()).collect();
+            let _ = writeln!(text, "{}", row);
+        }
+        text
+    }
+}
+
+// where the search has got to
+struct Search {
+    game: Game,
+    // lines left that the perfect clear has to fit in
+    lines: usize,
+    // pieces left in the game's queue that were known about, the rest are made up
+    known: usize,
+    // whether the game's current and held pieces are ones that were known about
+    current_known: bool,
+    held_known: bool,
+    steps: Vec<Step>,
+    shape: Vec<Vec<Tetromino>>,
+    pieces: Vec<PlacedPiece>,
+    // which of the shape's rows each of the bottom `lines` rows of the board is
+    rows: Vec<usize>,
+}
+
+// every perfect clear in up to `lines` lines, the ones that take fewest lines first
+// `board` shouldn't have `piece` on it, and `queue` is what comes after it in order
+pub fn solve(
+    board: &Board,
+    piece: &Piece,
+    held: Option<Tetromino>,
+    queue: &[Tetromino],
+    lines: usize,
+) -> Vec<Solution> {
+    let stack = board
+        .grid
+        .iter()
+        .position(|row| row.iter().any(|&cell| cell != Tetromino::E))
+        .map_or(0, |top| board.height() - top);
+    let mut solutions = Vec::new();
+    for lines in stack.max(1)..=lines.min(board.height()) {
+        solve_in(board, piece, held, queue, lines, &mut solutions);
+    }
+    solutions
+}
+
+// adds the perfect clears that fill exactly the bottom `lines` lines
+fn solve_in(
+    board: &Board,
+    piece: &Piece,
+    held: Option<Tetromino>,
+    queue: &[Tetromino],
+    lines: usize,
+    solutions: &mut Vec<Solution>,
+) {
+    let top = board.height() - lines;
+
+    // the piece and the queue are handed out first, so they come out in order
+    let mut sequence = vec![piece.tetromino];
+    sequence.extend_from_slice(queue);
+    let chooser = PieceChooser::with_sequence(queue.len().max(1), 0, Randomizer::Random, &sequence);
+    let mut game = Game::with_chooser(board.clone(), chooser);
+    // wherever the piece has got to
+    game.board.remove_piece(&game.piece);
+    game.piece = piece.clone();
+    game.board.add_piece(&game.piece);
+    game.held = held;
+
+    let shape = board.grid[top..]
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&cell| match cell {
+                    Tetromino::E => Tetromino::E,
+                    _ => Tetromino::G,
+                })
+                .collect()
+        })
+        .collect();
+    let search = Search {
+        game,
+        lines,
+        known: queue.len(),
+        current_known: true,
+        held_known: true,
+        steps: Vec::new(),
+        shape,
+        pieces: Vec::new(),
+        rows: (0..lines).collect(),
+    };
+    solve_from(&search, solutions, &mut HashSet::new(), &mut Vec::new());
+}
+
+// adds every solution from here on
+// `found` has the pieces of every perfect clear found in these lines, kept or not
+fn solve_from(
+    search: &Search,
+    solutions: &mut Vec<Solution>,
+    searched: &mut HashSet<Vec<u8>>,
+    found: &mut Vec<Vec<PlacedPiece>>,
+) {
+    let game = &search.game;
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    let top = board.height() - search.lines;
+    let empty = board.grid[top..]
+        .iter()
+        .flatten()
+        .filter(|&&cell| cell == Tetromino::E)
+        .count();
+    // every known piece can be placed, holding a piece nobody knows about to bring out the
+    // last one if it comes to it
+    let pieces = search.known
+        + search.current_known as usize
+        + (game.held.is_some() && search.held_known) as usize;
+    if empty % 4 != 0 || empty / 4 > pieces {
+        return;
+    }
+    // a column that's full all the way up is a wall no piece can get across, and clearing
+    // lines only makes it shorter, so whatever's left of it has to be filled on its own
+    let mut left = 0;
+    for x in 0..board.width() {
+        let column = board.grid[top..]
+            .iter()
+            .filter(|row| row[x] == Tetromino::E)
+            .count();
+        if column == 0 && left % 4 != 0 {
+            return;
+        }
+        left += column;
+    }
+
+    // the pieces to come are the same for the same number of known ones left
+    let mut key: Vec<u8> = board.grid[top..]
+        .iter()
+        .flatten()
+        .map(|&cell| cell as u8)
+        .collect();
+    let current = game.piece.tetromino as u8 * search.current_known as u8;
+    let held = game.held.map_or(0, |held| held as u8) * search.held_known as u8;
+    key.extend([search.lines as u8, current, held, search.known as u8]);
+    if !searched.insert(key) {
+        return;
+    }
+
+    for placement in placements(game) {
+        // the piece being placed has to be one that's known about
+        let holds = matches!(placement.moves.first(), Some(Move::Hold));
+        let holds_from_queue = holds && game.held.is_none();
+        let known_piece = match (holds, game.held) {
+            (false, _) => search.current_known,
+            (true, Some(_)) => search.held_known,
+            (true, None) => search.known > 0,
+        };
+        if !known_piece {
+            continue;
+        }
+        if placement
+            .piece
+            .get_coords()
+            .iter()
+            .any(|&(_, y)| y < top as isize)
+        {
+            continue;
+        }
+
+        let mut placed = board.clone();
+        placed.add_piece(&placement.piece);
+        let full: Vec<usize> = (top..placed.height())
+            .filter(|&y| placed.grid[y].iter().all(|&cell| cell != Tetromino::E))
+            .collect();
+
+        let mut game = search.game.clone();
+        for &mov in &placement.moves {
+            game.move_piece(mov);
+        }
+        let locked = game.spawn_next_piece();
+        let cleared = game.last_lock.perfect_clear;
+        if locked.is_err() && !cleared {
+            continue;
+        }
+        let known = search.known - holds_from_queue as usize;
+
+        let mut next = Search {
+            game,
+            lines: search.lines - full.len(),
+            // the next piece might be one nobody knows about yet, which can still be held
+            // to bring out a held piece that's known
+            known: known.saturating_sub(1),
+            current_known: known > 0,
+            held_known: if holds {
+                search.current_known
+            } else {
+                search.held_known
+            },
+            steps: search.steps.clone(),
+            shape: search.shape.clone(),
+            pieces: search.pieces.clone(),
+            rows: search.rows.clone(),
+        };
+        let cells = placement
+            .piece
+            .get_coords()
+            .map(|(x, y)| (x as usize, search.rows[y as usize - top]));
+        for &(x, row) in &cells {
+            next.shape[row][x] = placement.piece.tetromino;
+        }
+        next.pieces.push((placement.piece.tetromino, cells));
+        for &y in full.iter().rev() {
+            next.rows.remove(y - top);
+        }
+        next.steps.push(Step {
+            board: board.clone(),
+            placement,
+        });
+
+        if cleared {
+            // the same pieces in the same places, only placed in a different order
+            let same_places = found
+                .iter()
+                .any(|pieces| pieces.iter().all(|piece| next.pieces.contains(piece)));
+            if same_places {
+                continue;
+            }
+            found.push(next.pieces.clone());
+            // the same order with the pieces somewhere else
+            let order = next.pieces.iter().map(|(tetromino, _)| tetromino);
+            let same_order = solutions.iter().any(|solution| {
+                solution
+                    .pieces
+                    .iter()
+                    .map(|(tetromino, _)| tetromino)
+                    .eq(order.clone())
+            });
+            if !same_order {
+                solutions.push(Solution {
+                    steps: next.steps,
+                    shape: next.shape,
+                    pieces: next.pieces,
+                });
+            }
+        } else {
+            solve_from(&next, solutions, searched, found);
+        }
+    }
+}
+
+// the moves for the first piece of the quickest perfect clear in up to `lines` lines with the
+// pieces the game shows, if there is one
+pub fn first_moves(game: &Game, lines: usize) -> Option<Vec<Move>> {
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    let queue: Vec<Tetromino> = game
+        .piece_chooser
+        .next_pieces
+        .iter()
+        .rev()
+        .copied()
+        .collect();
+    let solutions = solve(&board, &game.piece, game.held, &queue, lines);
+    solutions
+        .iter()
+        .min_by_key(|solution| solution.steps.len())
+        .map(|solution| solution.steps[0].placement.moves.clone())
+}
+
+// a bot that goes for a perfect clear whenever it can see one coming with the pieces it
+// knows about, and plays like the heuristic bot the rest of the time
+#[derive(Default)]
+pub struct PerfectClearPlayer {
+    heuristic: HeuristicPlayer,
+}
+
+impl Player for PerfectClearPlayer {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        first_moves(game, PC_LINES).unwrap_or_else(|| self.heuristic.choose_moves(game))
+    }
+
+    fn thoughts(&self) -> Option<Thoughts> {
+        self.heuristic.thoughts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the orders of every perfect clear in 4 lines
+    fn orders(rows: &str, piece: Tetromino, queue: &str) -> Vec<String> {
+        let (mut board, _) = crate::notation::parse_board(rows, Some(4)).unwrap();
+        let piece = board.spawn_piece(piece).unwrap();
+        board.remove_piece(&piece);
+        let queue: Vec<Tetromino> = queue
+            .chars()
+            .map(|c| Tetromino::from_char(c).unwrap())
+            .collect();
+        let mut orders: Vec<String> = solve(&board, &piece, None, &queue, 4)
+            .iter()
+            .map(Solution::order)
+            .collect();
+        orders.sort();
+        orders
+    }
+
+    #[test]
+    fn finds_every_perfect_clear_in_a_small_gap() {
+        let rows = "GGGGGG..../GGGGGG..../GGGGGGGG../GGGGGGGG..".replace('/', "\n");
+        // the Is lying on top of the O or standing up next to it, which go in the same order
+        assert_eq!(orders(&rows, Tetromino::I, "IO"), ["IIO"]);
+        assert_eq!(orders(&rows, Tetromino::O, "OO"), ["OOO"]);
+        assert!(orders(&rows, Tetromino::T, "SZ").is_empty());
+    }
+
+    #[test]
+    fn finds_the_perfect_clears_after_a_pco() {
+        // the PCO's first bag with the T left over
+        let rows = "....SIIIIZ/....SSOOZZ/....JSOOZL/....JJJLLL".replace('/', "\n");
+        // leaving the Z in hold, or the O in the queue
+        assert_eq!(orders(&rows, Tetromino::T, "ZOLI"), ["TOLI", "ZTLI"]);
+        assert_eq!(
+            orders(&rows, Tetromino::T, "SZIL"),
+            ["STIL", "STZL", "SZTL"]
+        );
+        assert_eq!(
+            orders(&rows, Tetromino::T, "OIJL"),
+            ["OIJL", "OITJ", "OITL", "TIJL", "TOIJ", "TOIL"]
+        );
+        assert!(orders(&rows, Tetromino::T, "SSSS").is_empty());
+    }
+}
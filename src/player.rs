@@ -1,14 +1,108 @@
+use std::io;
+
+use crate::args::Args;
 use crate::game::*;
+use crate::heuristic::HeuristicPlayer;
+use crate::opener::{self, OpenerPlayer};
+use crate::pc::PerfectClearPlayer;
 use rand::Rng;
 
 pub trait Player {
-    fn choose_moves(&self, board: &Board, piece: &Piece) -> Vec<Move>;
+    fn choose_moves(&self, game: &Game) -> Vec<Move>;
+
+    // what the bot made of the last game it chose moves for, for the bots that can say
+    fn thoughts(&self) -> Option<Thoughts> {
+        None
+    }
+
+    // how the bot is doing, shown under the stats, like why it stopped playing if it did
+    fn status(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // called before each game in a tournament, for bots that keep track of the game they're
+    // playing between moves
+    fn new_game(&self) {}
+}
+
+// what a bot considered when it chose its moves, for drawing over the board
+#[derive(Clone, Default)]
+pub struct Thoughts {
+    // how many pieces had been placed in the game it was thinking about
+    pub pieces: usize,
+    // the best placements it looked at with their scores, best (the chosen one) first
+    pub candidates: Vec<(Piece, f64)>,
+    // each position the chosen piece goes through on its way there
+    pub path: Vec<Piece>,
+    // how the chosen placement scored, as each feature's name, value and weighted part
+    pub breakdown: Vec<(&'static str, f64, f64)>,
+}
+
+// the bots that come built in, by the name they're picked with
+pub const BUILT_IN: [&str; 4] = ["random", "heuristic", "pc", "opener"];
+
+// the bot asked for on the command line, the random one unless another was picked
+pub fn from_args(args: &Args) -> io::Result<Box<dyn Player + Send>> {
+    if let Some(command) = &args.tbp {
+        return by_name(&format!("tbp:{}", command));
+    }
+    if let Some(command) = &args.bot {
+        return by_name(&format!("bot:{}", command));
+    }
+    by_name(&args.player)
+}
+
+// a built in bot, `opener:<name>` for the opener bot playing one opener,
+// `bot:<command>` for an external one (see external.rs) or
+// `tbp:<command>` for one speaking the Tetris Bot Protocol (see tbp.rs)
+pub fn by_name(name: &str) -> io::Result<Box<dyn Player + Send>> {
+    match name {
+        "random" => return Ok(Box::new(RandomPlayer)),
+        "heuristic" => return Ok(Box::new(HeuristicPlayer::default())),
+        "pc" => return Ok(Box::new(PerfectClearPlayer::default())),
+        "opener" => return Ok(Box::new(OpenerPlayer::new(None))),
+        _ => (),
+    }
+    if let Some(name) = name.strip_prefix("opener:") {
+        return match opener::find(name) {
+            Some(opener) => Ok(Box::new(OpenerPlayer::new(Some(opener)))),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown opener `{}`", name),
+            )),
+        };
+    }
+    if let Some(command) = name.strip_prefix("tbp:") {
+        #[cfg(feature = "serde")]
+        return Ok(Box::new(crate::tbp::TbpPlayer::launch(command)?));
+        #[cfg(not(feature = "serde"))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't run `{}`, TBP bots need the serde feature", command),
+        ));
+    }
+    if let Some(command) = name.strip_prefix("bot:") {
+        #[cfg(feature = "serde")]
+        return Ok(Box::new(crate::external::ExternalPlayer::launch(command)?));
+        #[cfg(not(feature = "serde"))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "can't run `{}`, external bots need the serde feature",
+                command
+            ),
+        ));
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unknown bot `{}`", name),
+    ))
 }
 
 pub struct RandomPlayer;
 
 impl Player for RandomPlayer {
-    fn choose_moves(&self, board: &Board, piece: &Piece) -> Vec<Move> {
+    fn choose_moves(&self, _game: &Game) -> Vec<Move> {
         let mut moves = Vec::new();
         let mut rng = rand::thread_rng();
         // let mut piece = piece.clone();
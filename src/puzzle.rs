@@ -0,0 +1,245 @@
+// puzzles: a board to start on, a fixed run of pieces and a goal to reach with them
+//
+// puzzle files look like this:
+//
+//   tetris-rs puzzle 1
+//   name Tetris
+//   goal lines 4
+//   pieces I
+//   board
+//   GGGGGGGGG.
+//   GGGGGGGGG.
+//   GGGGGGGGG.
+//   GGGGGGGGG.
+//
+// the goal is one of
+//   lines <n>       clear n lines
+//   perfect-clear   clear lines until the board is empty
+//   t-spin <n>      a T-spin that clears n lines (so `t-spin 2` is a T-spin double)
+// the pieces come out in order, the first one is the piece the puzzle starts with,
+// and the puzzle is failed once they've all been placed without reaching the goal (one
+// left in the hold can still be swapped back out for whatever comes after them, but placing
+// one of those fails the puzzle)
+// the board is in the text notation (see notation.rs) and is bottom aligned
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::constants::*;
+use crate::game::*;
+use crate::notation::parse_board;
+
+pub const PUZZLE_VERSION: u32 = 1;
+const PUZZLE_HEADER: &str = "tetris-rs puzzle";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Goal {
+    Lines(usize),
+    PerfectClear,
+    TSpin(usize),
+}
+
+impl Goal {
+    fn parse(text: &str) -> Option<Goal> {
+        let count = |value: &str| value.parse().ok().filter(|&count| count > 0);
+        match text.split_once(' ') {
+            Some(("lines", lines)) => count(lines).map(Goal::Lines),
+            Some(("t-spin", lines)) => count(lines).filter(|&lines| lines <= 3).map(Goal::TSpin),
+            None if text == "perfect-clear" => Some(Goal::PerfectClear),
+            _ => None,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Goal::Lines(1) => "Clear 1 line".to_string(),
+            Goal::Lines(lines) => format!("Clear {} lines", lines),
+            Goal::PerfectClear => "Perfect clear".to_string(),
+            Goal::TSpin(1) => "T-spin single".to_string(),
+            Goal::TSpin(2) => "T-spin double".to_string(),
+            Goal::TSpin(_) => "T-spin triple".to_string(),
+        }
+    }
+
+    // checked every time a piece locks
+    pub fn is_met(self, game: &Game) -> bool {
+        match self {
+            Goal::Lines(lines) => game.lines >= lines,
+            Goal::PerfectClear => game.last_lock.perfect_clear,
+            Goal::TSpin(lines) => game.last_lock.t_spin && game.last_lock.lines == lines,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<Tetromino>,
+    pub board: Board,
+}
+
+impl Puzzle {
+    // a fresh attempt at the puzzle, `seed` picks the pieces after the puzzle's run out
+    pub fn new_game(&self, seed: u64) -> Game {
+        let chooser =
+            PieceChooser::with_sequence(NUM_NEXT_PIECES, seed, Randomizer::Random, &self.pieces);
+        Game::with_chooser(self.board.clone(), chooser)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Puzzle> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().enumerate();
+
+        let header = lines.next().map_or("", |(_, line)| line);
+        let version = header
+            .strip_prefix(PUZZLE_HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| invalid_data(1, "not a puzzle file"))?;
+        if version != PUZZLE_VERSION {
+            return Err(invalid_data(
+                1,
+                &format!("unsupported puzzle version {}", version),
+            ));
+        }
+
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = None;
+        for (i, line) in lines.by_ref() {
+            if line == "board" {
+                break;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data(i + 1, "expected `key value`"))?;
+            let bad_value = || invalid_data(i + 1, &format!("bad value for `{}`", key));
+            match key {
+                "name" => name = Some(value.to_string()),
+                "goal" => goal = Some(Goal::parse(value).ok_or_else(bad_value)?),
+                "pieces" => {
+                    let sequence = value
+                        .chars()
+                        .map(|c| {
+                            Tetromino::from_char(c)
+                                .filter(|t| !matches!(t, Tetromino::E | Tetromino::G))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|sequence| !sequence.is_empty())
+                        .ok_or_else(bad_value)?;
+                    pieces = Some(sequence);
+                }
+                _ => return Err(invalid_data(i + 1, &format!("unknown key `{}`", key))),
+            }
+        }
+
+        let missing = |key: &str| invalid(&format!("missing `{}`", key));
+        let rows: Vec<&str> = lines.map(|(_, line)| line).collect();
+        let (board, _) = parse_board(&rows.join("\n"), Some(DEFAULT_HEIGHT))
+            .map_err(|err| invalid(&format!("bad board: {}", err)))?;
+        Ok(Puzzle {
+            name: name.unwrap_or_else(|| {
+                path.file_stem()
+                    .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+            }),
+            goal: goal.ok_or_else(|| missing("goal"))?,
+            pieces: pieces.ok_or_else(|| missing("pieces"))?,
+            board,
+        })
+    }
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("puzzle line {}: {}", line, message),
+    )
+}
+
+// for problems with the puzzle as a whole rather than one of its lines
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("puzzle: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::session::Session;
+
+    fn example(name: &str) -> Puzzle {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("puzzles")
+            .join(name);
+        Puzzle::load(&path).unwrap()
+    }
+
+    fn session(puzzle: Puzzle) -> Session {
+        let mut session = Session::new(
+            puzzle.new_game(1),
+            Some("puzzles start from their own board"),
+            0.0,
+        );
+        session.puzzle = Some(puzzle);
+        session.mode = Mode::Puzzle;
+        session
+    }
+
+    // plays the moves, shifting the piece all the way right before dropping it, and locks it
+    fn place_right(session: &mut Session, moves: &[Move]) {
+        session.update(0.0, moves, None);
+        session.shift_to_wall(0.0, Move::Right);
+        session.update(0.0, &[Move::Drop], None);
+        session.update(0.0, &[], None);
+    }
+
+    #[test]
+    fn loads_the_example_puzzles() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("puzzles");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let puzzle = Puzzle::load(&path).unwrap();
+            assert!(!puzzle.pieces.is_empty(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn solves_the_tetris_puzzle() {
+        let mut session = session(example("tetris.txt"));
+        place_right(&mut session, &[Move::Rotate]);
+        assert!(session.game_over && session.finished);
+        assert_eq!(session.game.lines, 4);
+    }
+
+    #[test]
+    fn the_last_piece_can_come_out_of_the_hold() {
+        let mut puzzle = example("tetris.txt");
+        puzzle.pieces = vec![Tetromino::I, Tetromino::O];
+        let mut session = session(puzzle);
+        // the O goes on the stack out of the way, with the I held until last
+        session.update(0.0, &[Move::Hold], None);
+        session.update(0.0, &[Move::Drop], None);
+        session.update(0.0, &[], None);
+        assert!(!session.game_over);
+        assert_eq!(session.puzzle_pieces_left(), 1);
+        place_right(&mut session, &[Move::Hold, Move::Rotate]);
+        assert!(session.game_over && session.finished);
+    }
+
+    #[test]
+    fn placing_a_piece_the_puzzle_didnt_give_fails_it() {
+        let mut session = session(example("tetris.txt"));
+        place_right(&mut session, &[Move::Hold]);
+        assert!(session.game_over && !session.finished);
+    }
+
+    #[test]
+    fn whole_puzzle_errors_have_no_line_number() {
+        let path = std::env::temp_dir().join(format!("tetris-rs-{}-puzzle", std::process::id()));
+        fs::write(&path, "tetris-rs puzzle 1\npieces I\nboard\nGGGGGGGGG.\n").unwrap();
+        let err = Puzzle::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.to_string(), "puzzle: missing `goal`");
+    }
+}
@@ -0,0 +1,135 @@
+// ratings and confidence intervals for comparing bots in tournaments
+//
+// games are scored from one side, 1 for a win, 0.5 for a draw and 0 for a loss
+
+use std::f64::consts::{LN_10, PI};
+
+use crate::constants::*;
+
+// the z score for 95% confidence
+const Z_95: f64 = 1.96;
+
+// the chance of a side rated `rating` beating one rated `opponent`
+fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+// updates two Elo ratings after a game, one game at a time
+pub fn elo(ratings: [f64; 2], score: f64) -> [f64; 2] {
+    let change = ELO_K * (score - expected(ratings[0], ratings[1]));
+    [ratings[0] + change, ratings[1] - change]
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Glicko {
+    pub rating: f64,
+    // how unsure the rating is, about a third of the 95% interval's width either way
+    pub deviation: f64,
+}
+
+impl Default for Glicko {
+    fn default() -> Glicko {
+        Glicko {
+            rating: START_RATING,
+            deviation: START_DEVIATION,
+        }
+    }
+}
+
+impl Glicko {
+    // the rating after a rating period where these games (against opponents as they were
+    // rated going in, and the score from this side) were played
+    pub fn update(&self, games: &[(Glicko, f64)]) -> Glicko {
+        if games.is_empty() {
+            return *self;
+        }
+        let q = LN_10 / 400.0;
+        let g = |deviation: f64| 1.0 / (1.0 + 3.0 * (q * deviation / PI).powi(2)).sqrt();
+        let mut variance = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in games {
+            let g = g(opponent.deviation);
+            let expected = 1.0 / (1.0 + 10f64.powf(-g * (self.rating - opponent.rating) / 400.0));
+            variance += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let d_squared = 1.0 / (q * q * variance);
+        let precision = 1.0 / self.deviation.powi(2) + 1.0 / d_squared;
+        Glicko {
+            rating: self.rating + q / precision * improvement,
+            deviation: (1.0 / precision).sqrt(),
+        }
+    }
+
+    // the 95% interval either side of the rating
+    pub fn interval(&self) -> f64 {
+        Z_95 * self.deviation
+    }
+}
+
+// the 95% Wilson score interval for a rate out of `games`, as (low, high)
+pub fn wilson(rate: f64, games: usize) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let n = games as f64;
+    let z2 = Z_95 * Z_95;
+    let centre = (rate + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let spread = Z_95 / (1.0 + z2 / n) * (rate * (1.0 - rate) / n + z2 / (4.0 * n * n)).sqrt();
+    ((centre - spread).max(0.0), (centre + spread).min(1.0))
+}
+
+// the mean and the 95% interval either side of it
+pub fn mean_interval(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() == 1 {
+        return (mean, 0.0);
+    }
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (n - 1.0);
+    (mean, Z_95 * (variance / n).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_moves_both_sides_by_the_same_amount() {
+        // evenly matched, so the winner gets half of K
+        let [a, b] = elo([1500.0, 1500.0], 1.0);
+        assert!((a - (1500.0 + ELO_K / 2.0)).abs() < 1e-9);
+        assert!((b - (1500.0 - ELO_K / 2.0)).abs() < 1e-9);
+        // a draw against someone rated higher still gains
+        let [a, b] = elo([1400.0, 1600.0], 0.5);
+        assert!(a > 1400.0 && b < 1600.0);
+        assert!((a - 1400.0 - (1600.0 - b)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn glicko_matches_the_worked_example() {
+        // the example from Glickman's paper describing Glicko
+        let player = Glicko {
+            rating: 1500.0,
+            deviation: 200.0,
+        };
+        let opponent = |rating, deviation| Glicko { rating, deviation };
+        let after = player.update(&[
+            (opponent(1400.0, 30.0), 1.0),
+            (opponent(1550.0, 100.0), 0.0),
+            (opponent(1700.0, 300.0), 0.0),
+        ]);
+        assert!((after.rating - 1464.1).abs() < 0.1, "{}", after.rating);
+        assert!((after.deviation - 151.4).abs() < 0.1, "{}", after.deviation);
+        // no games changes nothing
+        let same = player.update(&[]);
+        assert_eq!((same.rating, same.deviation), (1500.0, 200.0));
+    }
+}
@@ -0,0 +1,60 @@
+// personal bests, kept in a text file with one `key value` line per record
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub struct Records {
+    entries: Vec<(String, f64)>,
+}
+
+impl Records {
+    // a missing file just means nothing has been recorded yet
+    pub fn load(path: &Path) -> io::Result<Records> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .rsplit_once(' ')
+                .and_then(|(key, value)| Some((key, value.parse().ok()?)))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("records line {}: expected `key value`", i + 1),
+                    )
+                })?;
+            entries.push((key.to_string(), value));
+        }
+        Ok(Records { entries })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::new();
+        for (key, value) in &self.entries {
+            out += &format!("{} {}\n", key, value);
+        }
+        fs::write(path, out)
+    }
+
+    pub fn get(&self, key: &str) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == key)
+            .map(|&(_, value)| value)
+    }
+
+    pub fn set(&mut self, key: &str, value: f64) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+}
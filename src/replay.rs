@@ -0,0 +1,432 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::constants::*;
+use crate::game::*;
+
+// bump this whenever the file layout or the meaning of an event changes
+pub const REPLAY_VERSION: u32 = 4;
+const REPLAY_HEADER: &str = "tetris-rs replay";
+
+// the rules a replay was recorded under, playback refuses files that don't match
+// (apart from the board size and randomizer, which playback just uses)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ruleset {
+    pub width: usize,
+    pub height: usize,
+    pub randomizer: Randomizer,
+    pub next_pieces: usize,
+    pub drop_time: f64,
+    pub placement_delay: f64,
+    pub tetromino_limit: usize,
+}
+
+impl Ruleset {
+    pub fn current(width: usize, height: usize, randomizer: Randomizer) -> Ruleset {
+        Ruleset {
+            width,
+            height,
+            randomizer,
+            next_pieces: NUM_NEXT_PIECES,
+            drop_time: DROP_TIME,
+            placement_delay: PLACEMENT_DELAY,
+            tetromino_limit: TETROMINO_LIMIT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ReplayEvent {
+    // the previous piece was placed and this one came out of the piece chooser
+    // (the very first piece isn't recorded since it comes straight from the seed)
+    Spawn(Tetromino),
+    // a move requested by the player (or bot)
+    Move(Move),
+    // the piece was pulled down by the drop timer
+    Gravity,
+    // a garbage row came up under the stack with its hole in this column
+    Garbage(usize),
+}
+
+// written as `spawn T`, `move Left`, `gravity` or `garbage 3`
+impl fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayEvent::Spawn(tetromino) => write!(f, "spawn {}", tetromino.to_char()),
+            ReplayEvent::Move(mov) => write!(f, "move {:?}", mov),
+            ReplayEvent::Gravity => write!(f, "gravity"),
+            ReplayEvent::Garbage(hole) => write!(f, "garbage {}", hole),
+        }
+    }
+}
+
+impl ReplayEvent {
+    pub fn parse(text: &str) -> Option<ReplayEvent> {
+        let mut parts = text.split(' ');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("spawn"), Some(name), None) => name
+                .chars()
+                .next()
+                .and_then(Tetromino::from_char)
+                .map(ReplayEvent::Spawn),
+            (Some("move"), Some(name), None) => parse_move(name).map(ReplayEvent::Move),
+            (Some("gravity"), None, None) => Some(ReplayEvent::Gravity),
+            (Some("garbage"), Some(hole), None) => hole.parse().ok().map(ReplayEvent::Garbage),
+            _ => None,
+        }
+    }
+
+    // plays the event on a game, returns false if the game topped out
+    pub fn apply(self, game: &mut Game) -> bool {
+        match self {
+            ReplayEvent::Spawn(_) => game.spawn_next_piece().is_ok(),
+            ReplayEvent::Move(mov) => {
+                game.move_piece(mov);
+                true
+            }
+            ReplayEvent::Gravity => {
+                game.move_piece(Move::Down);
+                true
+            }
+            ReplayEvent::Garbage(hole) => {
+                hole < game.board.width() && game.add_garbage(&[hole]).is_ok()
+            }
+        }
+    }
+}
+
+pub struct Replay {
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    // every event with the time (in seconds since the game started) it happened at
+    pub events: Vec<(f64, ReplayEvent)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, randomizer: Randomizer, width: usize, height: usize) -> Replay {
+        Replay {
+            seed,
+            ruleset: Ruleset::current(width, height, randomizer),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, time: f64, event: ReplayEvent) {
+        self.events.push((time, event));
+    }
+
+    // the game as it was before the first event
+    pub fn new_game(&self) -> Game {
+        let ruleset = &self.ruleset;
+        let board = Board::new(ruleset.width, ruleset.height);
+        Game::with_board(self.seed, ruleset.randomizer, board)
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |&(time, _)| time)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::new();
+        out += &format!("{} {}\n", REPLAY_HEADER, REPLAY_VERSION);
+        out += &format!("seed {}\n", self.seed);
+        out += &format!("width {}\n", self.ruleset.width);
+        out += &format!("height {}\n", self.ruleset.height);
+        out += &format!("randomizer {}\n", self.ruleset.randomizer.name());
+        out += &format!("next_pieces {}\n", self.ruleset.next_pieces);
+        out += &format!("drop_time {}\n", self.ruleset.drop_time);
+        out += &format!("placement_delay {}\n", self.ruleset.placement_delay);
+        out += &format!("tetromino_limit {}\n", self.ruleset.tetromino_limit);
+        out += "events\n";
+        for (time, event) in &self.events {
+            out += &format!("{:.4} {}\n", time, event);
+        }
+        fs::write(path, out)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().enumerate();
+
+        let header = lines.next().map_or("", |(_, line)| line);
+        let version = header
+            .strip_prefix(REPLAY_HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| invalid_data(1, "not a replay file"))?;
+        if version != REPLAY_VERSION {
+            return Err(invalid_data(
+                1,
+                &format!("unsupported replay version {}", version),
+            ));
+        }
+
+        let mut seed = None;
+        // replays from before there was a choice were all dealt at random
+        let mut ruleset = Ruleset::current(DEFAULT_WIDTH, DEFAULT_HEIGHT, Randomizer::Random);
+        for (i, line) in lines.by_ref() {
+            if line == "events" {
+                break;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data(i + 1, "expected `key value`"))?;
+            let bad_value = || invalid_data(i + 1, &format!("bad value for `{}`", key));
+            match key {
+                "seed" => seed = Some(value.parse().map_err(|_| bad_value())?),
+                "width" => ruleset.width = value.parse().map_err(|_| bad_value())?,
+                "height" => ruleset.height = value.parse().map_err(|_| bad_value())?,
+                "randomizer" => {
+                    ruleset.randomizer = Randomizer::from_name(value).ok_or_else(bad_value)?
+                }
+                "next_pieces" => ruleset.next_pieces = value.parse().map_err(|_| bad_value())?,
+                "drop_time" => ruleset.drop_time = value.parse().map_err(|_| bad_value())?,
+                "placement_delay" => {
+                    ruleset.placement_delay = value.parse().map_err(|_| bad_value())?
+                }
+                "tetromino_limit" => {
+                    ruleset.tetromino_limit = value.parse().map_err(|_| bad_value())?
+                }
+                _ => return Err(invalid_data(i + 1, &format!("unknown key `{}`", key))),
+            }
+        }
+        let seed = seed.ok_or_else(|| invalid("missing seed"))?;
+        if ruleset.width < MIN_SIZE || ruleset.height < MIN_SIZE {
+            return Err(invalid("board is too small"));
+        }
+
+        let mut events = Vec::new();
+        for (i, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let (time, event) = line.split_once(' ').unwrap_or((line, ""));
+            let time = time
+                .parse::<f64>()
+                .map_err(|_| invalid_data(i + 1, "bad timestamp"))?;
+            let event =
+                ReplayEvent::parse(event).ok_or_else(|| invalid_data(i + 1, "bad event"))?;
+            events.push((time, event));
+        }
+
+        Ok(Replay {
+            seed,
+            ruleset,
+            events,
+        })
+    }
+}
+
+pub fn parse_move(name: &str) -> Option<Move> {
+    match name {
+        "Left" => Some(Move::Left),
+        "Right" => Some(Move::Right),
+        "Down" => Some(Move::Down),
+        "Rotate" => Some(Move::Rotate),
+        "Drop" => Some(Move::Drop),
+        "Hold" => Some(Move::Hold),
+        _ => None,
+    }
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("replay line {}: {}", line, message),
+    )
+}
+
+// for problems with the replay as a whole rather than one of its lines
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("replay: {}", message))
+}
+
+// re-runs a recorded game through the engine
+// the game is rebuilt from the seed and fed the exact same events, so no timing is involved
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub game: Game,
+    pub time: f64,
+    pub speed: f64,
+    pub paused: bool,
+    pub game_over: bool,
+    // set if a piece came out that isn't the one that was recorded, for the frontend to show
+    pub desync: Option<String>,
+    // index of the next event to apply
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        let game = replay.new_game();
+        ReplayPlayer {
+            replay,
+            game,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+            game_over: false,
+            desync: None,
+            cursor: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.replay.events.len()
+    }
+
+    // advances the playback clock by `dt` real seconds
+    pub fn update(&mut self, dt: f64) {
+        if self.paused {
+            return;
+        }
+        self.time += dt * self.speed;
+        while !self.is_finished() && self.replay.events[self.cursor].0 <= self.time {
+            self.step();
+        }
+    }
+
+    // applies exactly one event and moves the clock up to it
+    pub fn step(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        let (time, event) = self.replay.events[self.cursor];
+        self.cursor += 1;
+        self.time = self.time.max(time);
+
+        if !event.apply(&mut self.game) {
+            self.game_over = true;
+        }
+        if let ReplayEvent::Spawn(tetromino) = event {
+            if self.game.piece.tetromino != tetromino && self.desync.is_none() {
+                self.desync = Some(format!(
+                    "Replay desync: expected {:?} at {:.2}s but the seed gave {:?}",
+                    tetromino, time, self.game.piece.tetromino
+                ));
+            }
+        }
+    }
+
+    // there's no way to undo a move so seeking backwards replays from the start
+    pub fn seek(&mut self, time: f64) {
+        let time = time.clamp(0.0, self.replay.duration());
+        if time < self.time {
+            self.game = self.replay.new_game();
+            self.cursor = 0;
+            self.game_over = false;
+        }
+        while !self.is_finished() && self.replay.events[self.cursor].0 <= time {
+            self.step();
+        }
+        self.time = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a few pieces played on a game the way a session records them
+    fn recorded_game(seed: u64, randomizer: Randomizer) -> (Replay, Game) {
+        let mut game = Game::with_board(seed, randomizer, Board::new(10, 20));
+        let mut replay = Replay::new(seed, randomizer, 10, 20);
+        // spread across the board so the stack stays low
+        let moves = [
+            vec![Move::Left; 4],
+            vec![Move::Rotate, Move::Right, Move::Right],
+            vec![Move::Hold, Move::Down],
+            vec![Move::Right; 4],
+            vec![Move::Rotate, Move::Left],
+        ];
+        for (i, moves) in moves.iter().cycle().take(8).enumerate() {
+            let time = i as f64 * 0.5;
+            let events = moves
+                .iter()
+                .map(|&mov| ReplayEvent::Move(mov))
+                .chain([ReplayEvent::Gravity, ReplayEvent::Move(Move::Drop)]);
+            for event in events {
+                event.apply(&mut game);
+                replay.record(time, event);
+            }
+            if i == 4 {
+                game.add_garbage(&[3]).unwrap();
+                replay.record(time + 0.1, ReplayEvent::Garbage(3));
+            }
+            game.spawn_next_piece().unwrap();
+            replay.record(time + 0.25, ReplayEvent::Spawn(game.piece.tetromino));
+        }
+        (replay, game)
+    }
+
+    #[test]
+    fn events_round_trip_through_text() {
+        for event in [
+            ReplayEvent::Spawn(Tetromino::T),
+            ReplayEvent::Move(Move::Hold),
+            ReplayEvent::Gravity,
+            ReplayEvent::Garbage(7),
+        ] {
+            let text = event.to_string();
+            let parsed = ReplayEvent::parse(&text).unwrap();
+            assert_eq!(parsed.to_string(), text);
+        }
+        for bad in [
+            "spawn",
+            "spawn X",
+            "move Jump",
+            "gravity 1",
+            "garbage -1",
+            "",
+        ] {
+            assert!(ReplayEvent::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn a_saved_replay_plays_back_the_same_game() {
+        for randomizer in [Randomizer::Random, Randomizer::Bag] {
+            plays_back_the_same_game(randomizer);
+        }
+    }
+
+    fn plays_back_the_same_game(randomizer: Randomizer) {
+        let (replay, game) = recorded_game(42, randomizer);
+        let path = std::env::temp_dir().join(format!(
+            "tetris-rs-{}-{}.replay",
+            std::process::id(),
+            randomizer.name()
+        ));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.ruleset, replay.ruleset);
+        let text = |replay: &Replay| -> Vec<String> {
+            replay
+                .events
+                .iter()
+                .map(|(time, event)| format!("{:.4} {}", time, event))
+                .collect()
+        };
+        assert_eq!(text(&loaded), text(&replay));
+
+        let mut player = ReplayPlayer::new(loaded);
+        player.update(replay.duration() + 1.0);
+        assert!(player.is_finished());
+        assert!(player.desync.is_none());
+        assert!(player.game.board.grid == game.board.grid);
+        assert!(player.game.piece == game.piece);
+    }
+
+    #[test]
+    fn a_replay_from_another_seed_is_a_desync() {
+        let (mut replay, _) = recorded_game(42, Randomizer::Random);
+        replay.seed = 43;
+        let mut player = ReplayPlayer::new(replay);
+        player.seek(f64::MAX);
+        assert!(player.desync.is_some());
+    }
+}
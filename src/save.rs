@@ -0,0 +1,424 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::constants::*;
+use crate::game::*;
+use crate::mode::Mode;
+use crate::session::Session;
+
+pub const SAVE_VERSION: u32 = 5;
+const SAVE_HEADER: &str = "tetris-rs save";
+
+// everything needed to pick a game back up exactly where it was left
+pub struct SavedGame {
+    pub game: Game,
+    // seconds since the last gravity tick
+    pub gravity_elapsed: f64,
+    // seconds since the piece touched down, if it's waiting to be placed
+    pub placement_elapsed: Option<f64>,
+    // the mode being played and how many seconds into it the game was, with the splits so far
+    pub mode: Mode,
+    pub elapsed: f64,
+    pub splits: Vec<f64>,
+    // the garbage still to come up and the rows cleared in dig mode, the holes picked so far,
+    // and the rows that have come up in survival mode along with when the next one is due
+    pub garbage_left: usize,
+    pub garbage_cleared: usize,
+    pub garbage_holes: u64,
+    pub garbage_sent: usize,
+    pub next_garbage: f64,
+}
+
+impl SavedGame {
+    pub fn new(session: &Session, time: f64) -> SavedGame {
+        let game = &session.game;
+        SavedGame {
+            game: game.clone(),
+            gravity_elapsed: time - session.prev_time,
+            placement_elapsed: game.board.is_placed_time.map(|start| time - start),
+            mode: session.mode,
+            elapsed: session.elapsed(time),
+            splits: session.splits.clone(),
+            garbage_left: session.garbage_left,
+            garbage_cleared: session.garbage_cleared,
+            garbage_holes: session.garbage_holes(),
+            garbage_sent: session.garbage_sent,
+            next_garbage: session.next_garbage,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: &Path) -> io::Result<SavedGame> {
+        SavedGame::from_text(&fs::read_to_string(path)?)
+    }
+
+    // the save file's contents, which is also how games are sent over the network
+    pub fn to_text(&self) -> String {
+        let game = &self.game;
+        let piece = &game.piece;
+
+        let mut out = String::new();
+        out += &format!("{} {}\n", SAVE_HEADER, SAVE_VERSION);
+        out += &format!("seed {}\n", game.piece_chooser.seed());
+        out += &format!("randomizer {}\n", game.piece_chooser.randomizer().name());
+        out += &format!("draws {}\n", game.piece_chooser.draws());
+        out += &format!(
+            "next_pieces {}\n",
+            game.piece_chooser
+                .next_pieces
+                .iter()
+                .map(|tetromino| tetromino.to_char())
+                .collect::<String>()
+        );
+        out += &format!("score {}\n", game.board.score);
+        out += &format!("num_tetrominos {}\n", game.num_tetrominos);
+        out += &format!("lines {}\n", game.lines);
+        out += &format!(
+            "clears {}\n",
+            game.clears.map(|count| count.to_string()).join(" ")
+        );
+        out += &format!(
+            "piece {} {} {} {:?}\n",
+            piece.tetromino.to_char(),
+            piece.x,
+            piece.y,
+            piece.orientation
+        );
+        match game.held {
+            Some(held) => out += &format!("held {}\n", held.to_char()),
+            None => out += "held none\n",
+        }
+        out += &format!("can_hold {}\n", game.can_hold);
+        out += &format!("gravity_elapsed {}\n", self.gravity_elapsed);
+        match self.placement_elapsed {
+            Some(elapsed) => out += &format!("placement_elapsed {}\n", elapsed),
+            None => out += "placement_elapsed none\n",
+        }
+        out += &format!("mode {}\n", self.mode.key());
+        out += &format!("elapsed {}\n", self.elapsed);
+        if !self.splits.is_empty() {
+            let splits: Vec<String> = self.splits.iter().map(|split| split.to_string()).collect();
+            out += &format!("splits {}\n", splits.join(" "));
+        }
+        out += &format!("garbage_left {}\n", self.garbage_left);
+        out += &format!("garbage_cleared {}\n", self.garbage_cleared);
+        out += &format!("garbage_holes {}\n", self.garbage_holes);
+        out += &format!("garbage_sent {}\n", self.garbage_sent);
+        out += &format!("next_garbage {}\n", self.next_garbage);
+        out += "grid\n";
+        for row in game.board.grid.iter() {
+            out += &row.iter().map(|cell| cell.to_char()).collect::<String>();
+            out += "\n";
+        }
+        out
+    }
+
+    pub fn from_text(text: &str) -> io::Result<SavedGame> {
+        let mut lines = text.lines().enumerate();
+
+        let header = lines.next().map_or("", |(_, line)| line);
+        let version = header
+            .strip_prefix(SAVE_HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| invalid_data(1, "not a save file"))?;
+        if version != SAVE_VERSION {
+            return Err(invalid_data(
+                1,
+                &format!("unsupported save version {}", version),
+            ));
+        }
+
+        let mut seed = None;
+        // dealt at random unless the save says otherwise
+        let mut randomizer = Randomizer::Random;
+        let mut draws = None;
+        let mut next_pieces = None;
+        let mut piece = None;
+        let mut score = 0;
+        let mut num_tetrominos = 0;
+        let mut lines_cleared = 0;
+        let mut clears = [0; 4];
+        let mut held = None;
+        let mut can_hold = true;
+        let mut gravity_elapsed = 0.0;
+        let mut placement_elapsed = None;
+        let mut mode = None;
+        let mut elapsed = 0.0;
+        let mut splits = Vec::new();
+        let mut garbage_left = 0;
+        let mut garbage_cleared = 0;
+        let mut garbage_holes = 0;
+        let mut garbage_sent = 0;
+        let mut next_garbage = SURVIVAL_INTERVAL;
+        for (i, line) in lines.by_ref() {
+            if line == "grid" {
+                break;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data(i + 1, "expected `key value`"))?;
+            let bad_value = || invalid_data(i + 1, &format!("bad value for `{}`", key));
+            match key {
+                "seed" => seed = Some(value.parse().map_err(|_| bad_value())?),
+                "randomizer" => randomizer = Randomizer::from_name(value).ok_or_else(bad_value)?,
+                "draws" => draws = Some(value.parse().map_err(|_| bad_value())?),
+                "next_pieces" => {
+                    next_pieces = Some(
+                        value
+                            .chars()
+                            .map(parse_tetromino)
+                            .collect::<Option<Vec<_>>>()
+                            .filter(|pieces| !pieces.is_empty())
+                            .ok_or_else(bad_value)?,
+                    )
+                }
+                "score" => score = value.parse().map_err(|_| bad_value())?,
+                "num_tetrominos" => num_tetrominos = value.parse().map_err(|_| bad_value())?,
+                "lines" => lines_cleared = value.parse().map_err(|_| bad_value())?,
+                "clears" => {
+                    clears = value
+                        .split(' ')
+                        .map(|count| count.parse().ok())
+                        .collect::<Option<Vec<_>>>()
+                        .and_then(|counts| counts.try_into().ok())
+                        .ok_or_else(bad_value)?
+                }
+                "piece" => piece = Some(parse_piece(value).ok_or_else(bad_value)?),
+                "held" => {
+                    held = match value {
+                        "none" => None,
+                        _ => Some(
+                            value
+                                .chars()
+                                .next()
+                                .and_then(parse_tetromino)
+                                .ok_or_else(bad_value)?,
+                        ),
+                    }
+                }
+                "can_hold" => can_hold = value.parse().map_err(|_| bad_value())?,
+                "gravity_elapsed" => gravity_elapsed = value.parse().map_err(|_| bad_value())?,
+                "placement_elapsed" => {
+                    placement_elapsed = match value {
+                        "none" => None,
+                        _ => Some(value.parse().map_err(|_| bad_value())?),
+                    }
+                }
+                "mode" => mode = Some(Mode::from_key(value).ok_or_else(bad_value)?),
+                "elapsed" => elapsed = value.parse().map_err(|_| bad_value())?,
+                "splits" => {
+                    splits = value
+                        .split(' ')
+                        .map(|split| split.parse().ok())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(bad_value)?
+                }
+                "garbage_left" => garbage_left = value.parse().map_err(|_| bad_value())?,
+                "garbage_cleared" => garbage_cleared = value.parse().map_err(|_| bad_value())?,
+                "garbage_holes" => garbage_holes = value.parse().map_err(|_| bad_value())?,
+                "garbage_sent" => garbage_sent = value.parse().map_err(|_| bad_value())?,
+                "next_garbage" => next_garbage = value.parse().map_err(|_| bad_value())?,
+                _ => return Err(invalid_data(i + 1, &format!("unknown key `{}`", key))),
+            }
+        }
+
+        // the board is as big as the grid that was saved
+        let mut grid: Vec<Vec<Tetromino>> = Vec::new();
+        for (i, line) in lines {
+            let row = line
+                .chars()
+                .map(Tetromino::from_char)
+                .collect::<Option<Vec<_>>>()
+                .filter(|row| row.len() >= MIN_SIZE)
+                .filter(|row| grid.first().is_none_or(|first| first.len() == row.len()))
+                .ok_or_else(|| invalid_data(i + 1, "bad grid row"))?;
+            grid.push(row);
+        }
+        if grid.len() < MIN_SIZE + HIDDEN_ROWS {
+            return Err(invalid("not enough grid rows"));
+        }
+        let board = Board {
+            grid,
+            score,
+            ..Board::new(0, 0)
+        };
+
+        let missing = |key: &str| invalid(&format!("missing `{}`", key));
+        let piece = piece.ok_or_else(|| missing("piece"))?;
+        let on_board = piece.get_coords().iter().all(|&(x, y)| {
+            (0..board.width() as isize).contains(&x) && (0..board.height() as isize).contains(&y)
+        });
+        if !on_board {
+            return Err(invalid("the piece is off the board"));
+        }
+        let piece_chooser = PieceChooser::restore(
+            seed.ok_or_else(|| missing("seed"))?,
+            randomizer,
+            draws.ok_or_else(|| missing("draws"))?,
+            next_pieces.ok_or_else(|| missing("next_pieces"))?,
+        );
+        Ok(SavedGame {
+            game: Game {
+                board,
+                piece,
+                piece_chooser,
+                num_tetrominos,
+                lines: lines_cleared,
+                clears,
+                held,
+                can_hold,
+                last_move: None,
+                last_lock: Lock::default(),
+            },
+            gravity_elapsed,
+            placement_elapsed,
+            mode: mode.ok_or_else(|| missing("mode"))?,
+            elapsed,
+            splits,
+            garbage_left,
+            garbage_cleared,
+            garbage_holes,
+            garbage_sent,
+            next_garbage,
+        })
+    }
+
+    // hands back the game with its timers set relative to `time`
+    // returns the game along with the time of the last gravity tick
+    pub fn resume(&self, time: f64) -> (Game, f64) {
+        let mut game = self.game.clone();
+        game.board.is_placed_time = self.placement_elapsed.map(|elapsed| time - elapsed);
+        (game, time - self.gravity_elapsed)
+    }
+}
+
+fn parse_piece(value: &str) -> Option<Piece> {
+    let mut parts = value.split(' ');
+    let tetromino = parts.next()?.chars().next().and_then(parse_tetromino)?;
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    let orientation = match parts.next()? {
+        "Up" => Orientation::Up,
+        "Right" => Orientation::Right,
+        "Down" => Orientation::Down,
+        "Left" => Orientation::Left,
+        _ => return None,
+    };
+    Some(Piece {
+        tetromino,
+        x,
+        y,
+        orientation,
+    })
+}
+
+// one of the seven pieces, which is all the queue, hold and falling piece can be
+fn parse_tetromino(c: char) -> Option<Tetromino> {
+    Tetromino::from_char(c).filter(|&tetromino| !matches!(tetromino, Tetromino::E | Tetromino::G))
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("save line {}: {}", line, message),
+    )
+}
+
+// for problems with the save as a whole rather than one of its lines
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("save: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tetris-rs-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trips_a_game_in_progress() {
+        let game = Game::with_board(7, Randomizer::Bag, Board::new(10, 20));
+        let mut session = Session::new(game, None, 0.0);
+        session.mode = Mode::Dig {
+            lines: 12,
+            messiness: 0.5,
+        };
+        session.garbage_left = 4;
+        session.garbage_cleared = 3;
+        session.splits = vec![1.5, 2.25];
+        session.game.move_piece(Move::Hold);
+        session.game.move_piece(Move::Drop);
+        session.game.spawn_next_piece().unwrap();
+
+        let path = temp_path("round-trip.sav");
+        session.save(&path, 10.0).unwrap();
+        let saved = SavedGame::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (game, loaded) = (&session.game, &saved.game);
+        assert!(loaded.board.grid == game.board.grid);
+        assert!(loaded.piece == game.piece);
+        assert_eq!(
+            loaded.piece_chooser.next_pieces,
+            game.piece_chooser.next_pieces
+        );
+        assert_eq!(loaded.piece_chooser.draws(), game.piece_chooser.draws());
+        assert_eq!(loaded.piece_chooser.randomizer(), Randomizer::Bag);
+        // the rest of the pieces come out the same
+        let (mut chooser, mut loaded_chooser) =
+            (game.piece_chooser.clone(), loaded.piece_chooser.clone());
+        for _ in 0..20 {
+            assert_eq!(loaded_chooser.get_next_piece(), chooser.get_next_piece());
+        }
+        assert_eq!(loaded.held, game.held);
+        assert_eq!(loaded.num_tetrominos, game.num_tetrominos);
+        assert_eq!(loaded.board.score, game.board.score);
+        assert_eq!(saved.mode, session.mode);
+        assert_eq!(saved.elapsed, 10.0);
+        assert_eq!(saved.splits, session.splits);
+        assert_eq!(saved.garbage_left, 4);
+        assert_eq!(saved.garbage_cleared, 3);
+    }
+
+    #[test]
+    fn rejects_saves_that_would_break_the_game() {
+        let session = Session::new(Game::new(7, 10, 20), None, 0.0);
+        let path = temp_path("broken.sav");
+        session.save(&path, 0.0).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let piece_line = text
+            .lines()
+            .find(|line| line.starts_with("piece "))
+            .unwrap();
+        let broken = [
+            ("next_pieces ", "next_pieces "),
+            ("next_pieces ", "next_pieces IGO"),
+            (piece_line, "piece G 3 3 Up"),
+            (piece_line, "piece T 40 3 Up"),
+            (piece_line, "piece T 3 -5 Up"),
+        ];
+        for (from, to) in broken {
+            let broken: String = text
+                .lines()
+                .map(|line| {
+                    if line.starts_with(from) {
+                        to.to_string()
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            fs::write(&path, broken).unwrap();
+            let err = SavedGame::load(&path).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", to);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+// serde support for the core game types, only compiled with the `serde` feature
+//
+// the JSON shape is meant to be read by other tools so it should stay stable:
+//
+//   Tetromino    a one letter string, "I" "O" "T" "S" "Z" "J" "L", "G" for garbage or "." for empty
+//   Orientation  "Up" | "Right" | "Down" | "Left"
+//   Move         "Left" | "Right" | "Down" | "Rotate" | "Drop" | "Hold"
+//   GameState    "Menu" | "Playing" | "GameOver"
+//   Piece        {"tetromino": "T", "x": 0, "y": 0, "orientation": "Up"}
+//                x and y are the board coordinates of the top left of the piece's 4x4 box
+//   Board        {"rows": ["..........", ..., "IIIIJJJ..."], "score": 0}
+//                one string per row from the top of the board down, one cell per character
+//                (the board is as wide and tall as the rows given, the first 4 rows are
+//                hidden above the visible field)
+//                using the same letters as Tetromino (so the active piece shows up in it too)
+//
+// anything that only matters while a game is running (placement timers etc.) is left out
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::constants::*;
+use crate::game::{Board, Tetromino};
+
+impl Serialize for Tetromino {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.to_char())
+    }
+}
+
+impl<'de> Deserialize<'de> for Tetromino {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let c = char::deserialize(deserializer)?;
+        Tetromino::from_char(c)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown tetromino `{}`", c)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BoardJson {
+    rows: Vec<String>,
+    score: u32,
+}
+
+impl From<Board> for BoardJson {
+    fn from(board: Board) -> BoardJson {
+        BoardJson {
+            rows: board
+                .grid
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.to_char()).collect())
+                .collect(),
+            score: board.score,
+        }
+    }
+}
+
+impl TryFrom<BoardJson> for Board {
+    type Error = String;
+
+    fn try_from(json: BoardJson) -> Result<Board, String> {
+        if json.rows.len() < MIN_SIZE + HIDDEN_ROWS {
+            return Err(format!(
+                "expected at least {} rows but got {}",
+                MIN_SIZE + HIDDEN_ROWS,
+                json.rows.len()
+            ));
+        }
+
+        let mut grid: Vec<Vec<Tetromino>> = Vec::new();
+        for (y, row) in json.rows.iter().enumerate() {
+            let cells = row
+                .chars()
+                .map(Tetromino::from_char)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("unknown cell in row {}", y))?;
+            let width = grid.first().map_or(cells.len(), |first| first.len());
+            if cells.len() != width || width < MIN_SIZE {
+                return Err(format!(
+                    "expected {} cells in row {} but got {}",
+                    width.max(MIN_SIZE),
+                    y,
+                    cells.len()
+                ));
+            }
+            grid.push(cells);
+        }
+        let board = Board {
+            grid,
+            score: json.score,
+            ..Board::new(0, 0)
+        };
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boards_round_trip_through_json() {
+        let mut board = Board::new(6, 8);
+        let floor = board.height() - 1;
+        board.grid[floor] = "IIGG.T"
+            .chars()
+            .map(|c| Tetromino::from_char(c).unwrap())
+            .collect();
+        board.grid[floor - 1][5] = Tetromino::L;
+        board.score = 1200;
+
+        let json = serde_json::to_string(&board).unwrap();
+        assert!(json.starts_with("{\"rows\":[\"......\""));
+        assert!(json.ends_with("\"IIGG.T\"],\"score\":1200}"));
+        let back: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.grid, board.grid);
+        assert_eq!(back.score, board.score);
+    }
+
+    #[test]
+    fn bad_boards_are_rejected() {
+        let rows = |rows: &[&str]| {
+            let rows: Vec<String> = rows.iter().map(|row| format!("\"{}\"", row)).collect();
+            format!("{{\"rows\":[{}],\"score\":0}}", rows.join(","))
+        };
+        let full = vec!["....."; MIN_SIZE + HIDDEN_ROWS];
+        assert!(serde_json::from_str::<Board>(&rows(&full)).is_ok());
+        // too few rows
+        assert!(serde_json::from_str::<Board>(&rows(&full[1..])).is_err());
+        // an unknown cell, and a row that's the wrong width
+        for bad in ["..X..", "......"] {
+            let mut board = full.clone();
+            board[3] = bad;
+            assert!(serde_json::from_str::<Board>(&rows(&board)).is_err());
+        }
+    }
+}
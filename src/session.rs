@@ -0,0 +1,847 @@
+use std::io;
+use std::path::Path;
+
+use crate::args::Args;
+use crate::constants::*;
+use crate::finesse::Finesse;
+use crate::fumen::{self, FumenPage};
+use crate::game::*;
+use crate::garbage::GarbageGenerator;
+use crate::mode::{drop_time, survival_interval, Mode};
+use crate::notation::parse_board;
+use crate::player::Player;
+use crate::puzzle::Puzzle;
+use crate::records::Records;
+use crate::replay::{Replay, ReplayEvent};
+use crate::save::SavedGame;
+
+// a game along with everything that's kept track of while it's played
+// the frontends only deal with input and drawing, and pass the time in
+pub struct Session {
+    pub game: Game,
+    pub replay: Replay,
+    // every placed piece, for exporting to fumen
+    pub placements: Vec<FumenPage>,
+    // why the game can't be recorded, if it can't (replays start from an empty board and only
+    // go forwards)
+    pub unreplayable: Option<&'static str>,
+    // replay timestamps are relative to this
+    pub start_time: f64,
+    // when gravity last pulled the piece down
+    pub prev_time: f64,
+    // moves the bot has decided on but not played yet
+    pub chosen_moves: Vec<Move>,
+    pub game_over: bool,
+    pub mode: Mode,
+    // set when the game ended by reaching the mode's goal rather than topping out
+    pub finished: bool,
+    // when the game ended, so the timer stops there
+    pub end_time: Option<f64>,
+    // seconds into the game each `SPLIT_LINES` lines were reached
+    pub splits: Vec<f64>,
+    // the personal best for this mode from before this game started
+    pub best: Option<f64>,
+    garbage: GarbageGenerator,
+    // garbage rows in dig mode that haven't come up yet, and the ones that have been cleared
+    pub garbage_left: usize,
+    pub garbage_cleared: usize,
+    // garbage rows that have come up in survival mode, and when (in seconds into the game)
+    // the next one is due
+    pub garbage_sent: usize,
+    pub next_garbage: f64,
+    // the puzzle being played in puzzle mode, kept so it can be restarted, and whether the
+    // current and held pieces are ones it gave
+    pub puzzle: Option<Puzzle>,
+    pub puzzle_current: bool,
+    pub puzzle_held: bool,
+    // in zen mode, the game as it was when each piece so far came out (the last one being
+    // the current piece), and the ones taken back by undoing that can be redone
+    pub history: Vec<Game>,
+    pub undone: Vec<Game>,
+    // how efficiently the pieces placed by hand were moved into place
+    pub finesse: Finesse,
+}
+
+impl Session {
+    pub fn new(game: Game, unreplayable: Option<&'static str>, time: f64) -> Session {
+        let seed = game.piece_chooser.seed();
+        let puzzle_current = game.piece_chooser.bag_pieces_out() == 0;
+        Session {
+            replay: Replay::new(
+                seed,
+                game.piece_chooser.randomizer(),
+                game.board.width(),
+                game.board.visible_height(),
+            ),
+            game,
+            placements: Vec::new(),
+            unreplayable,
+            start_time: time,
+            prev_time: time,
+            chosen_moves: Vec::new(),
+            game_over: false,
+            mode: Mode::Endless,
+            finished: false,
+            end_time: None,
+            splits: Vec::new(),
+            best: None,
+            garbage: GarbageGenerator::new(seed, DIG_MESSINESS),
+            garbage_left: 0,
+            garbage_cleared: 0,
+            garbage_sent: 0,
+            next_garbage: SURVIVAL_INTERVAL,
+            puzzle: None,
+            puzzle_current,
+            puzzle_held: false,
+            history: Vec::new(),
+            undone: Vec::new(),
+            finesse: Finesse::default(),
+        }
+    }
+
+    // sets up the first game from the command line options
+    pub fn from_args(args: &Args, time: f64) -> Result<Session, String> {
+        if let Some(path) = &args.resume {
+            let saved = SavedGame::load(path)
+                .map_err(|err| format!("Couldn't load saved game: {}", err))?;
+            let mut session = Session::new(Game::new(0, args.width, args.height), None, time);
+            session.resume(saved, args.manual, time);
+            return Ok(session);
+        }
+
+        let seed = args.seed.unwrap_or_else(rand::random);
+        if let Some(path) = &args.puzzle {
+            let puzzle =
+                Puzzle::load(path).map_err(|err| format!("Couldn't load puzzle: {}", err))?;
+            let mut session = Session::new(
+                puzzle.new_game(seed),
+                Some("puzzles start from their own board"),
+                time,
+            );
+            session.puzzle = Some(puzzle);
+            session.set_mode(args.mode, args.manual);
+            return Ok(session);
+        }
+
+        let board = if let Some(data) = &args.fumen {
+            let pages =
+                fumen::decode(data).map_err(|err| format!("Couldn't load fumen: {}", err))?;
+            pages.first().map(|page| page.board.clone())
+        } else if let Some(path) = &args.board {
+            let (board, _) = std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| parse_board(&text, Some(args.height)))
+                .map_err(|err| format!("Couldn't load board: {}", err))?;
+            Some(board)
+        } else {
+            None
+        };
+
+        let mut session = match board {
+            Some(board) => Session::new(
+                Game::with_board(seed, args.randomizer, board),
+                Some("it started from a board"),
+                time,
+            ),
+            None => {
+                let board = Board::new(args.width, args.height);
+                Session::new(Game::with_board(seed, args.randomizer, board), None, time)
+            }
+        };
+        session.set_mode(args.mode, args.manual);
+        Ok(session)
+    }
+
+    // switches to `mode` and looks up the personal best to compare against
+    pub fn set_mode(&mut self, mode: Mode, manual: bool) {
+        self.mode = mode;
+        self.load_best(manual);
+        self.set_up_mode();
+    }
+
+    fn load_best(&mut self, manual: bool) {
+        self.best = Records::load(Path::new(RECORDS_FILE))
+            .ok()
+            .and_then(|records| records.get(&self.records_key(manual)));
+    }
+
+    // gets a new game ready for the mode
+    fn set_up_mode(&mut self) {
+        if let Mode::Dig { lines, messiness } = self.mode {
+            self.garbage = GarbageGenerator::new(self.game.piece_chooser.seed(), messiness);
+            self.garbage_left = lines;
+            self.refill_garbage(self.start_time);
+        }
+        if self.mode == Mode::Zen {
+            self.unreplayable = Some("undoing can't be replayed");
+            self.history = vec![self.game.clone()];
+        }
+    }
+
+    // starts over with a fresh game on a board the same size as this one
+    // (or from the start of the puzzle)
+    pub fn restart(&mut self, time: f64) {
+        let (width, height) = (self.game.board.width(), self.game.board.visible_height());
+        let randomizer = self.game.piece_chooser.randomizer();
+        let best = if self.is_new_best() {
+            self.record()
+        } else {
+            self.best
+        };
+        let mode = self.mode;
+        let puzzle = self.puzzle.take();
+        *self = match &puzzle {
+            Some(puzzle) => Session::new(
+                puzzle.new_game(rand::random()),
+                Some("puzzles start from their own board"),
+                time,
+            ),
+            None => {
+                let board = Board::new(width, height);
+                let game = Game::with_board(rand::random(), randomizer, board);
+                Session::new(game, None, time)
+            }
+        };
+        self.mode = mode;
+        self.best = best;
+        self.puzzle = puzzle;
+        self.set_up_mode();
+    }
+
+    // carries on with a saved game, in the mode it was being played in and with the clock
+    // where it was
+    pub fn resume(&mut self, saved: SavedGame, manual: bool, time: f64) {
+        let (game, prev_time) = saved.resume(time);
+        let unreplayable = Some("it was resumed from a save");
+        *self = Session::new(game, unreplayable, time - saved.elapsed);
+        self.prev_time = prev_time;
+        self.mode = saved.mode;
+        self.load_best(manual);
+        self.splits = saved.splits;
+        let messiness = match saved.mode {
+            Mode::Dig { messiness, .. } => messiness,
+            _ => DIG_MESSINESS,
+        };
+        self.garbage = GarbageGenerator::restore(
+            self.game.piece_chooser.seed(),
+            messiness,
+            saved.garbage_holes,
+            self.game.board.width(),
+        );
+        self.garbage_left = saved.garbage_left;
+        self.garbage_cleared = saved.garbage_cleared;
+        self.garbage_sent = saved.garbage_sent;
+        self.next_garbage = saved.next_garbage;
+        if self.mode == Mode::Zen {
+            self.history = vec![self.game.clone()];
+        }
+    }
+
+    // restarts the clocks, for when the game sat in a menu before being played
+    pub fn start(&mut self, time: f64) {
+        self.start_time = time;
+        self.prev_time = time;
+    }
+
+    pub fn is_out_of_pieces(&self) -> bool {
+        self.game.num_tetrominos >= TETROMINO_LIMIT
+    }
+
+    pub fn save(&self, path: &Path, time: f64) -> io::Result<()> {
+        // saves only keep the seed, which doesn't give back a puzzle's pieces
+        if self.puzzle.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "puzzles can't be saved",
+            ));
+        }
+        SavedGame::new(self, time).save(path)
+    }
+
+    // how many garbage holes have been picked, for saving
+    pub fn garbage_holes(&self) -> u64 {
+        self.garbage.holes()
+    }
+
+    // runs one frame of the game
+    // `moves` are the moves the player made this frame, and `player` is the bot (if any)
+    // returns what the piece that locked this frame did, if one did
+    pub fn update(
+        &mut self,
+        time: f64,
+        moves: &[Move],
+        player: Option<&dyn Player>,
+    ) -> Option<Lock> {
+        if self.game_over {
+            return None;
+        }
+        let elapsed = time - self.start_time;
+        if let Some(time_limit) = self.mode.time_limit().filter(|&limit| elapsed >= limit) {
+            self.end(self.start_time + time_limit, true);
+            return None;
+        }
+        if self.mode == Mode::Survival && elapsed >= self.next_garbage {
+            self.send_garbage(time, 1);
+            self.garbage_sent += 1;
+            self.next_garbage += survival_interval(self.garbage_sent);
+            if self.game_over {
+                return None;
+            }
+        }
+
+        // check if the previous piece is placed
+        let mut locked = None;
+        if self.game.board.is_placed(&self.game.piece, time) || self.game.board.just_dropped {
+            let mut board = self.game.board.clone();
+            board.remove_piece(&self.game.piece);
+            if player.is_none() {
+                self.finesse.lock(&board, &self.game.piece);
+            }
+            self.placements.push(FumenPage {
+                board,
+                piece: Some(self.game.piece.clone()),
+                comment: format!("score {}", self.game.board.score),
+                lock: true,
+            });
+
+            // only the puzzle's own pieces can be placed, any others are just there to be held
+            let placed_puzzle_piece = self.puzzle_current;
+            let garbage_rows = self.game.board.garbage_rows();
+            let spawned = self.game.spawn_next_piece();
+            self.garbage_cleared += garbage_rows.saturating_sub(self.game.board.garbage_rows());
+            locked = Some(self.game.last_lock);
+            self.puzzle_current = self.game.piece_chooser.bag_pieces_out() == 0;
+            if spawned.is_ok() {
+                self.replay
+                    .record(elapsed, ReplayEvent::Spawn(self.game.piece.tetromino));
+            }
+
+            if let Some(goal) = self.mode.line_goal() {
+                // marathons are too long for splits to be worth showing
+                let reached = self.game.lines.min(goal) / SPLIT_LINES;
+                while self.mode == Mode::Sprint && self.splits.len() < reached {
+                    self.splits.push(elapsed);
+                }
+                if self.game.lines >= goal {
+                    self.end(time, true);
+                    return locked;
+                }
+            }
+
+            if let Mode::Dig { .. } = self.mode {
+                if self.garbage_left == 0 && self.game.board.garbage_rows() == 0 {
+                    self.end(time, true);
+                    return locked;
+                }
+                if spawned.is_ok() {
+                    self.refill_garbage(time);
+                }
+            }
+
+            if let Some(puzzle) = &self.puzzle {
+                if placed_puzzle_piece && puzzle.goal.is_met(&self.game) {
+                    self.end(time, true);
+                    return locked;
+                }
+                if !placed_puzzle_piece || self.puzzle_pieces_left() == 0 {
+                    self.end(time, false);
+                    return locked;
+                }
+            }
+
+            if self.mode == Mode::Zen {
+                if spawned.is_err() {
+                    self.game.clear_board();
+                    self.game.num_tetrominos += 1;
+                }
+                self.history.push(self.game.clone());
+                self.undone.clear();
+            }
+
+            // the placed or new piece (or the garbage) topped out
+            if spawned.is_err() && self.mode != Mode::Zen {
+                self.end(time, false);
+            }
+            if self.game_over {
+                return locked;
+            }
+        }
+
+        if self.mode != Mode::Zen && time - self.prev_time > drop_time(self.level()) {
+            self.game.move_piece(Move::Down);
+            self.replay.record(elapsed, ReplayEvent::Gravity);
+            self.prev_time = time;
+        }
+
+        for &mov in moves {
+            let could_hold = self.game.can_hold;
+            self.play_move(elapsed, mov);
+            // the piece that comes out of the hold starts from nothing
+            if matches!(mov, Move::Hold) && could_hold && !self.game.can_hold {
+                self.finesse.inputs = 0;
+            } else {
+                self.finesse.input(mov);
+            }
+        }
+
+        if let Some(player) = player {
+            if !self.chosen_moves.is_empty() {
+                let mov = self.chosen_moves.remove(0);
+                self.play_move(elapsed, mov);
+            } else {
+                self.chosen_moves = player.choose_moves(&self.game);
+            }
+        }
+        locked
+    }
+
+    // moves the piece as far as it goes, for a left or right key that's been held down
+    // (which is only the one input, counted when it was pressed)
+    pub fn shift_to_wall(&mut self, time: f64, mov: Move) {
+        if self.game_over {
+            return;
+        }
+        let elapsed = time - self.start_time;
+        loop {
+            let before = self.game.piece.clone();
+            self.game.move_piece(mov);
+            if self.game.piece == before {
+                break;
+            }
+            self.replay.record(elapsed, ReplayEvent::Move(mov));
+        }
+    }
+
+    // pushes a garbage row under the stack, for practising digging
+    // (only in endless and zen, where there's nothing to spoil)
+    pub fn add_garbage(&mut self, time: f64) {
+        if !self.game_over && matches!(self.mode, Mode::Endless | Mode::Zen) {
+            self.send_garbage(time, 1);
+        }
+    }
+
+    // tops the board back up to `DIG_ROWS` garbage rows while there are any left to dig
+    fn refill_garbage(&mut self, time: f64) {
+        let missing = DIG_ROWS
+            .saturating_sub(self.game.board.garbage_rows())
+            .min(self.garbage_left);
+        if missing > 0 {
+            self.garbage_left -= missing;
+            self.send_garbage(time, missing);
+        }
+    }
+
+    fn send_garbage(&mut self, time: f64, rows: usize) {
+        let width = self.game.board.width();
+        let holes: Vec<usize> = (0..rows).map(|_| self.garbage.next_hole(width)).collect();
+        self.push_garbage(time, &holes);
+    }
+
+    // garbage sent over by an opponent, which all has its hole in the same column
+    pub fn receive_garbage(&mut self, time: f64, rows: usize) {
+        let hole = self.garbage.next_hole(self.game.board.width());
+        self.push_garbage(time, &vec![hole; rows]);
+    }
+
+    fn push_garbage(&mut self, time: f64, holes: &[usize]) {
+        let elapsed = time - self.start_time;
+        for &hole in holes {
+            self.replay.record(elapsed, ReplayEvent::Garbage(hole));
+        }
+        if self.game.add_garbage(holes).is_err() {
+            if self.mode == Mode::Zen {
+                self.game.clear_board();
+            } else {
+                self.end(time, false);
+            }
+        }
+    }
+
+    // takes back the last placement in zen mode
+    pub fn undo(&mut self) {
+        if self.history.len() > 1 {
+            self.undone.extend(self.history.pop());
+            self.restore(self.history.last().unwrap().clone());
+        }
+    }
+
+    // puts back the last placement that was undone
+    pub fn redo(&mut self) {
+        if let Some(game) = self.undone.pop() {
+            self.history.push(game.clone());
+            self.restore(game);
+        }
+    }
+
+    fn restore(&mut self, game: Game) {
+        self.game = game;
+        // the piece gets its full placement delay again, and its inputs are counted again
+        self.game.board.is_placed_time = None;
+        self.finesse.inputs = 0;
+        self.chosen_moves.clear();
+    }
+
+    // changes the piece in a slot of the next queue (0 being the piece that comes out next)
+    // to the next tetromino along, in zen mode
+    pub fn cycle_next_piece(&mut self, slot: usize) {
+        let next_pieces = &mut self.game.piece_chooser.next_pieces;
+        if self.mode != Mode::Zen || slot >= next_pieces.len() {
+            return;
+        }
+        let index = next_pieces.len() - 1 - slot;
+        let tetrominos = [
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::Z,
+            Tetromino::J,
+            Tetromino::L,
+        ];
+        let current = tetrominos
+            .iter()
+            .position(|&tetromino| tetromino == next_pieces[index])
+            .unwrap_or(0);
+        next_pieces[index] = tetrominos[(current + 1) % tetrominos.len()];
+
+        // the queue no longer leads to the placements that were undone
+        if let Some(turn_start) = self.history.last_mut() {
+            turn_start.piece_chooser.next_pieces = next_pieces.clone();
+        }
+        self.undone.clear();
+        self.chosen_moves.clear();
+    }
+
+    // the puzzle's pieces that haven't been placed yet, counting one in the hold
+    pub fn puzzle_pieces_left(&self) -> usize {
+        let Some(puzzle) = &self.puzzle else {
+            return 0;
+        };
+        if self.game.piece_chooser.bag_pieces_out() == 0 {
+            // every piece placed so far was one of the puzzle's
+            puzzle.pieces.len().saturating_sub(self.game.num_tetrominos)
+        } else {
+            self.puzzle_current as usize + self.puzzle_held as usize
+        }
+    }
+
+    // how many garbage rows have been cleared in dig mode
+    pub fn garbage_dug(&self) -> usize {
+        match self.mode {
+            Mode::Dig { lines, .. } => self.garbage_cleared.min(lines),
+            _ => 0,
+        }
+    }
+
+    pub fn end(&mut self, time: f64, finished: bool) {
+        self.game_over = true;
+        self.finished = finished;
+        self.end_time = Some(time);
+    }
+
+    // what the game over screen is headed with
+    pub fn title(&self) -> &'static str {
+        match (self.finished, self.mode) {
+            (true, Mode::Puzzle) => "Solved!",
+            (true, _) => "Finished!",
+            (false, _) => "Game over!",
+        }
+    }
+
+    // seconds since the game started, stopping when it ends
+    pub fn elapsed(&self, time: f64) -> f64 {
+        self.end_time.unwrap_or(time) - self.start_time
+    }
+
+    pub fn pieces_per_second(&self, time: f64) -> f64 {
+        let elapsed = self.elapsed(time);
+        if elapsed > 0.0 {
+            self.game.num_tetrominos as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    // how long the game took, once it's over
+    fn final_time(&self) -> Option<f64> {
+        self.end_time.map(|end_time| end_time - self.start_time)
+    }
+
+    // what a finished game goes up against the personal best with, if anything
+    fn record(&self) -> Option<f64> {
+        // survival always ends in a top out, it's just a question of when
+        if !self.finished && self.mode != Mode::Survival {
+            return None;
+        }
+        match self.mode {
+            Mode::Endless | Mode::Versus | Mode::Puzzle | Mode::Zen => None,
+            Mode::Sprint | Mode::Dig { .. } | Mode::Survival => self.final_time(),
+            Mode::Ultra { .. } | Mode::Marathon { .. } => Some(self.game.board.score as f64),
+        }
+    }
+
+    fn is_new_best(&self) -> bool {
+        let Some(record) = self.record() else {
+            return false;
+        };
+        self.best.is_none_or(|best| {
+            if self.mode.lower_is_better() {
+                record < best
+            } else {
+                record > best
+            }
+        })
+    }
+
+    // bots and people get their own personal bests
+    fn records_key(&self, manual: bool) -> String {
+        let player = if manual { "manual" } else { "bot" };
+        format!("{} {}", self.mode.key(), player)
+    }
+
+    pub fn level(&self) -> usize {
+        self.mode.level(self.game.lines)
+    }
+
+    // what's shown next to the board while playing
+    pub fn stats(&self, time: f64) -> Vec<String> {
+        let lines = match self.mode.line_goal() {
+            Some(goal) => format!("Lines {}/{}", self.game.lines, goal),
+            None => format!("Lines {}", self.game.lines),
+        };
+        let clock = match self.mode.time_limit() {
+            Some(time_limit) => format!("Left {}", format_time(time_limit - self.elapsed(time))),
+            None => format!("Time {}", format_time(self.elapsed(time))),
+        };
+        let mut stats = vec![clock, lines];
+        match self.mode {
+            Mode::Marathon { .. } => stats.push(format!("Level {}", self.level())),
+            Mode::Dig { lines, .. } => stats.push(format!("Dug {}/{}", self.garbage_dug(), lines)),
+            Mode::Survival => stats.push(format!("Rows {}", self.garbage_sent)),
+            _ => (),
+        }
+        if self.puzzle.is_some() {
+            stats.push(format!("Pieces {}", self.puzzle_pieces_left()));
+        }
+        stats.push(format!("PPS {:.2}", self.pieces_per_second(time)));
+        stats
+    }
+
+    // the breakdown shown once the game is over
+    pub fn results(&self) -> Vec<String> {
+        let end_time = self.end_time.unwrap_or(self.prev_time);
+        let elapsed = self.elapsed(end_time);
+        let mut results = vec![format!("Time {}", format_time(elapsed))];
+        for (i, split) in self.splits.iter().enumerate() {
+            results.push(format!(
+                "{} lines  {}",
+                (i + 1) * SPLIT_LINES,
+                format_time(*split)
+            ));
+        }
+        results.push(format!(
+            "{} pieces  {:.2} PPS",
+            self.game.num_tetrominos,
+            self.pieces_per_second(end_time)
+        ));
+        if let Mode::Marathon { .. } = self.mode {
+            results.push(format!("Score {}", self.game.board.score));
+            results.push(format!("Level {}", self.level()));
+        }
+        if let Mode::Dig { lines, .. } = self.mode {
+            results.push(format!("Garbage dug {}/{}", self.garbage_dug(), lines));
+        }
+        if self.mode == Mode::Survival {
+            results.push(format!("Garbage rows survived {}", self.garbage_sent));
+        }
+        if let Some(puzzle) = &self.puzzle {
+            results.push(format!("Puzzle {}", puzzle.name));
+            results.push(format!("Goal {}", puzzle.goal.describe()));
+            if !self.finished {
+                results.push("Out of pieces".to_string());
+            }
+        }
+        if let Mode::Ultra { .. } = self.mode {
+            results.push(format!("Score {}", self.game.board.score));
+            let names = ["Singles", "Doubles", "Triples", "Tetrises"];
+            let mut clear_points = 0;
+            for (i, (name, &count)) in names.iter().zip(&self.game.clears).enumerate() {
+                let points = count as u32 * clear_score(i + 1);
+                clear_points += points;
+                results.push(format!("{} {}  {}", name, count, points));
+            }
+            // everything else came from soft and hard drops
+            results.push(format!(
+                "Drops  {}",
+                self.game.board.score.saturating_sub(clear_points)
+            ));
+        }
+        results.extend(self.finesse.results());
+        if let Some(record) = self.record() {
+            results.push(match self.best {
+                Some(best) => {
+                    let (shown, difference) = if self.mode.is_timed() {
+                        (format_time(best), format!("{:+.3}s", record - best))
+                    } else {
+                        (best.to_string(), format!("{:+}", record - best))
+                    };
+                    if self.is_new_best() {
+                        format!("New personal best! ({})", difference)
+                    } else {
+                        format!("Personal best {} ({})", shown, difference)
+                    }
+                }
+                None => "New personal best!".to_string(),
+            });
+        }
+        results
+    }
+
+    fn play_move(&mut self, elapsed: f64, mov: Move) {
+        let (could_hold, held) = (self.game.can_hold, self.game.held);
+        self.game.move_piece(mov);
+        self.replay.record(elapsed, ReplayEvent::Move(mov));
+        if matches!(mov, Move::Hold) && could_hold && !self.game.can_hold {
+            let from_queue = self.game.piece_chooser.bag_pieces_out() == 0;
+            let current = if held.is_some() {
+                self.puzzle_held
+            } else {
+                from_queue
+            };
+            self.puzzle_held = self.puzzle_current;
+            self.puzzle_current = current;
+        }
+    }
+
+    // writes out the replay and fumen if they were asked for
+    // returns what happened so the frontend can show it
+    pub fn finish(&self, args: &Args) -> Vec<String> {
+        let mut messages = Vec::new();
+        if let Some(record) = self.record().filter(|_| self.is_new_best()) {
+            let path = Path::new(RECORDS_FILE);
+            let saved = Records::load(path).and_then(|mut records| {
+                records.set(&self.records_key(args.manual), record);
+                records.save(path)
+            });
+            if let Err(err) = saved {
+                messages.push(format!("Couldn't save personal best: {}", err));
+            }
+        }
+        if let Some(path) = &args.record {
+            messages.push(match self.unreplayable {
+                Some(reason) => format!("Replay not saved: {}", reason),
+                None => match self.replay.save(path) {
+                    Ok(()) => format!("Replay saved to {}", path.display()),
+                    Err(err) => format!("Couldn't save replay: {}", err),
+                },
+            });
+        }
+        if let Some(path) = &args.export_fumen {
+            messages.push(
+                match std::fs::write(path, fumen::encode(&self.placements)) {
+                    Ok(()) => format!("Fumen saved to {}", path.display()),
+                    Err(err) => format!("Couldn't save fumen: {}", err),
+                },
+            );
+        }
+        messages
+    }
+}
+
+// minutes, seconds and milliseconds, like 1:23.456
+pub fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zen_session() -> Session {
+        let mut session = Session::new(Game::new(11, 10, 20), None, 0.0);
+        session.set_mode(Mode::Zen, true);
+        session
+    }
+
+    // moves the piece, hard drops it and lets it lock
+    fn place(session: &mut Session, moves: &[Move]) {
+        let moves = [moves, &[Move::Drop]].concat();
+        session.update(0.0, &moves, None);
+        session.update(0.0, &[], None);
+    }
+
+    // everything undoing and redoing should put back
+    fn snapshot(
+        game: &Game,
+    ) -> (
+        Vec<Vec<Tetromino>>,
+        Piece,
+        Vec<Tetromino>,
+        Option<Tetromino>,
+        usize,
+    ) {
+        (
+            game.board.grid.clone(),
+            game.piece.clone(),
+            game.piece_chooser.next_pieces.clone(),
+            game.held,
+            game.num_tetrominos,
+        )
+    }
+
+    #[test]
+    fn undo_puts_back_the_board_piece_and_queue() {
+        let mut session = zen_session();
+        let start = snapshot(&session.game);
+        place(&mut session, &[Move::Left, Move::Left]);
+        let before = snapshot(&session.game);
+        place(&mut session, &[Move::Hold, Move::Right]);
+        assert!(snapshot(&session.game) != before);
+
+        session.undo();
+        assert!(snapshot(&session.game) == before);
+        session.undo();
+        assert!(snapshot(&session.game) == start);
+        // there's nothing before the first piece to go back to
+        session.undo();
+        assert!(snapshot(&session.game) == start);
+    }
+
+    #[test]
+    fn redo_after_undo_is_exact() {
+        let mut session = zen_session();
+        place(&mut session, &[Move::Left, Move::Left]);
+        place(&mut session, &[Move::Rotate, Move::Right]);
+        let after = snapshot(&session.game);
+
+        session.undo();
+        session.undo();
+        session.redo();
+        session.redo();
+        assert!(snapshot(&session.game) == after);
+        // and once everything's redone there's nothing more to redo
+        session.redo();
+        assert!(snapshot(&session.game) == after);
+    }
+
+    #[test]
+    fn a_new_placement_clears_what_can_be_redone() {
+        let mut session = zen_session();
+        place(&mut session, &[Move::Left, Move::Left]);
+        place(&mut session, &[Move::Right]);
+        session.undo();
+        assert_eq!(session.undone.len(), 1);
+
+        place(&mut session, &[Move::Rotate]);
+        assert!(session.undone.is_empty());
+        let placed = snapshot(&session.game);
+        session.redo();
+        assert!(snapshot(&session.game) == placed);
+    }
+}
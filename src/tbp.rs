@@ -0,0 +1,446 @@
+// lets bots that speak the Tetris Bot Protocol (TBP) play, only compiled with the `serde`
+// feature since the protocol is JSON
+//
+// the bot is run as a subprocess and sent one JSON message per line on its stdin, and
+// answers the same way on its stdout (see external.rs for how the process is run):
+//   bot:  {"type": "info", "name": ..., "version": ..., "author": ..., "features": [...]}
+//   us:   {"type": "rules"}
+//   bot:  {"type": "ready"} or {"type": "error", "reason": ...}
+// then for every piece:
+//   us:   {"type": "start", "hold": null, "queue": ["T", ...], "combo": 0,
+//          "back_to_back": false, "board": [[null, "G", ...], ...]}  only when the bot
+//          doesn't already know what the game looks like (the first piece, or after garbage,
+//          a restart or a suggestion that couldn't be played), after a {"type": "stop"}
+//   us:   {"type": "new_piece", "piece": "L"}  for each piece that's come into the queue
+//   us:   {"type": "suggest"}
+//   bot:  {"type": "suggestion", "moves": [{"location": {...}, "spin": "none"}, ...]}
+//   us:   {"type": "play", "move": ...}  with the first suggestion that can be reached
+// and {"type": "quit"} at the end
+//
+// TBP boards are 40 rows from the bottom up, and locations are the piece's rotation centre
+// (x from the left, y from the bottom), so ours are turned upside down on the way through
+// bots expect 10 columns, other widths are sent as they are and it's up to the bot
+
+use std::cell::RefCell;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::external::BotProcess;
+use crate::game::*;
+use crate::movegen::placements;
+use crate::player::Player;
+
+// how many rows a TBP board has
+const TBP_ROWS: usize = 40;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<Tetromino>,
+        // the current piece first
+        queue: Vec<Tetromino>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<Tetromino>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mov: TbpMove,
+    },
+    NewPiece {
+        piece: Tetromino,
+    },
+    Quit,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<TbpMove>,
+    },
+    // anything newer than this frontend knows about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TbpMove {
+    location: Location,
+    // "none", "mini" or "full", only passed back to the bot
+    spin: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Location {
+    #[serde(rename = "type")]
+    tetromino: Tetromino,
+    orientation: TbpOrientation,
+    x: isize,
+    y: isize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TbpOrientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Location {
+    // the board cells the piece covers
+    fn cells(&self, board: &Board) -> [(isize, isize); 4] {
+        // relative to the rotation centre pointing north, with y going up
+        let minos = match self.tetromino {
+            Tetromino::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Tetromino::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Tetromino::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Tetromino::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Tetromino::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Tetromino::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Tetromino::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+            Tetromino::E | Tetromino::G => [(0, 0); 4],
+        };
+        let bottom = board.height() as isize - 1;
+        minos.map(|(x, y)| {
+            let (x, y) = match self.orientation {
+                TbpOrientation::North => (x, y),
+                TbpOrientation::East => (y, -x),
+                TbpOrientation::South => (-x, -y),
+                TbpOrientation::West => (-y, x),
+            };
+            (self.x + x, bottom - (self.y + y))
+        })
+    }
+}
+
+// the board without the falling piece in it
+fn settled_grid(game: &Game) -> Vec<Vec<Tetromino>> {
+    let mut board = game.board.clone();
+    board.remove_piece(&game.piece);
+    board.grid
+}
+
+// the current piece followed by the ones in the next queue, in the order they'll come
+fn full_queue(game: &Game) -> Vec<Tetromino> {
+    let next = game.piece_chooser.next_pieces.iter().rev();
+    std::iter::once(game.piece.tetromino)
+        .chain(next.copied())
+        .collect()
+}
+
+fn tbp_board(grid: &[Vec<Tetromino>]) -> Vec<Vec<Option<Tetromino>>> {
+    let width = grid.first().map_or(0, |row| row.len());
+    (0..TBP_ROWS)
+        .map(|y| match grid.len().checked_sub(y + 1) {
+            Some(row) => grid[row]
+                .iter()
+                .map(|&cell| (cell != Tetromino::E).then_some(cell))
+                .collect(),
+            None => vec![None; width],
+        })
+        .collect()
+}
+
+// what the bot thinks the game looks like after the last move it was told was played
+struct Known {
+    grid: Vec<Vec<Tetromino>>,
+    held: Option<Tetromino>,
+    queue: Vec<Tetromino>,
+}
+
+struct Bot {
+    process: BotProcess,
+    known: Option<Known>,
+    // why the bot stopped playing, if it has, and the last thing it wrote to stderr
+    error: Option<String>,
+    last_words: Option<String>,
+}
+
+impl Bot {
+    fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        self.process.send_line(&serde_json::to_string(message)?)
+    }
+
+    // the next message from the bot that this frontend understands
+    fn receive(&mut self) -> io::Result<BotMessage> {
+        loop {
+            let line = self.process.receive_line()?;
+            match serde_json::from_str(&line)? {
+                BotMessage::Unknown => continue,
+                BotMessage::Error { reason } => return Err(invalid_data(&reason)),
+                message => return Ok(message),
+            }
+        }
+    }
+
+    fn suggest(&mut self, game: &Game) -> io::Result<Vec<Move>> {
+        let grid = settled_grid(game);
+        let queue = full_queue(game);
+        match self.known.take() {
+            Some(known)
+                if known.grid == grid
+                    && known.held == game.held
+                    && queue.starts_with(&known.queue) =>
+            {
+                for &piece in &queue[known.queue.len()..] {
+                    self.send(&FrontendMessage::NewPiece { piece })?;
+                }
+            }
+            known => {
+                if known.is_some() {
+                    self.send(&FrontendMessage::Stop)?;
+                }
+                self.send(&FrontendMessage::Start {
+                    hold: game.held,
+                    queue: queue.clone(),
+                    combo: 0,
+                    back_to_back: false,
+                    board: tbp_board(&grid),
+                })?;
+            }
+        }
+
+        self.send(&FrontendMessage::Suggest)?;
+        let suggestions = loop {
+            if let BotMessage::Suggestion { moves } = self.receive()? {
+                break moves;
+            }
+        };
+        for suggestion in suggestions {
+            let location = &suggestion.location;
+            let cells = location.cells(&game.board);
+            let Some(path) = moves_to(game, location.tetromino, cells) else {
+                continue;
+            };
+            self.send(&FrontendMessage::Play { mov: suggestion })?;
+
+            // the bot takes the played piece out of its queue, and the one after it too if
+            // that went into an empty hold
+            let mut after = game.clone();
+            for &mov in &path {
+                after.move_piece(mov);
+            }
+            let used = if game.held.is_none() && matches!(path[0], Move::Hold) {
+                2
+            } else {
+                1
+            };
+            if after.spawn_next_piece().is_ok() {
+                self.known = Some(Known {
+                    grid: settled_grid(&after),
+                    held: after.held,
+                    queue: queue[used..].to_vec(),
+                });
+            }
+            return Ok(path);
+        }
+        // nothing the bot wants can be done here, so it's started over on the next piece
+        Ok(vec![Move::Drop])
+    }
+}
+
+pub struct TbpPlayer {
+    pub name: String,
+    bot: RefCell<Bot>,
+}
+
+impl TbpPlayer {
+    // runs `command` and waits for the bot to be ready
+    pub fn launch(command: &str) -> io::Result<TbpPlayer> {
+        let mut bot = Bot {
+            process: BotProcess::spawn(command)?,
+            known: None,
+            error: None,
+            last_words: None,
+        };
+        let BotMessage::Info {
+            name,
+            version,
+            author,
+        } = bot.receive()?
+        else {
+            return Err(invalid_data("the bot didn't introduce itself"));
+        };
+        bot.send(&FrontendMessage::Rules)?;
+        let BotMessage::Ready = bot.receive()? else {
+            return Err(invalid_data("the bot didn't get ready"));
+        };
+        Ok(TbpPlayer {
+            name: format!("{} {} by {}", name, version, author),
+            bot: RefCell::new(bot),
+        })
+    }
+}
+
+impl Player for TbpPlayer {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        let mut bot = self.bot.borrow_mut();
+        if bot.error.is_none() {
+            match bot.suggest(game) {
+                Ok(moves) => return moves,
+                Err(err) => {
+                    bot.last_words = bot.process.stop().pop();
+                    bot.error = Some(err.to_string());
+                }
+            }
+        }
+        // a bot that's gone just hard drops everything
+        vec![Move::Drop]
+    }
+
+    fn status(&self) -> Vec<String> {
+        let bot = self.bot.borrow();
+        let Some(error) = &bot.error else {
+            return Vec::new();
+        };
+        let mut status = vec![format!("{} stopped: {}", self.name, error)];
+        status.extend(bot.last_words.clone());
+        status
+    }
+
+    // the bot's told to stop, so it's started on the new game's first piece
+    fn new_game(&self) {
+        let mut bot = self.bot.borrow_mut();
+        if bot.error.is_none() && bot.known.take().is_some() {
+            let _ = bot.send(&FrontendMessage::Stop);
+        }
+    }
+}
+
+impl Drop for TbpPlayer {
+    fn drop(&mut self) {
+        // it's asked nicely first, but doesn't get to hang around after the game
+        let _ = self.bot.get_mut().send(&FrontendMessage::Quit);
+    }
+}
+
+// the moves that take the piece onto exactly these cells and lock it there, holding first
+// if it's for the other tetromino, or None if it can't get there
+fn moves_to(game: &Game, tetromino: Tetromino, cells: [(isize, isize); 4]) -> Option<Vec<Move>> {
+    let mut cells = cells;
+    cells.sort();
+    placements(game)
+        .into_iter()
+        .find(|placement| placement.piece.tetromino == tetromino && placement.cells() == cells)
+        .map(|placement| placement.moves)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a game on an empty board with this piece falling
+    fn game_with(tetromino: Tetromino) -> Game {
+        let chooser = PieceChooser::with_sequence(1, 0, Randomizer::Random, &[tetromino]);
+        Game::with_chooser(Board::new(10, 20), chooser)
+    }
+
+    // cells moved into the top left corner, in order
+    fn shape(cells: [(isize, isize); 4]) -> [(isize, isize); 4] {
+        let left = cells.iter().map(|&(x, _)| x).min().unwrap();
+        let top = cells.iter().map(|&(_, y)| y).min().unwrap();
+        let mut shape = cells.map(|(x, y)| (x - left, y - top));
+        shape.sort();
+        shape
+    }
+
+    #[test]
+    fn locations_cover_the_cells_the_piece_ends_up_on() {
+        let tetrominos = [
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::Z,
+            Tetromino::J,
+            Tetromino::L,
+        ];
+        let orientations = [
+            TbpOrientation::North,
+            TbpOrientation::East,
+            TbpOrientation::South,
+            TbpOrientation::West,
+        ];
+        for tetromino in tetrominos {
+            for orientation in orientations {
+                let game = game_with(tetromino);
+                let mut location = Location {
+                    tetromino,
+                    orientation,
+                    x: 4,
+                    y: 0,
+                };
+                // sitting on the floor, so a hard drop gets it there
+                let lowest = location.cells(&game.board).map(|(_, y)| y);
+                location.y = lowest.iter().max().unwrap() - (game.board.height() as isize - 1);
+                let cells = location.cells(&game.board);
+
+                let what = format!("{:?} {:?}", tetromino, orientation);
+                // the same shape as our piece turned the same way
+                let turned = match orientation {
+                    TbpOrientation::North => Orientation::Up,
+                    TbpOrientation::East => Orientation::Right,
+                    TbpOrientation::South => Orientation::Down,
+                    TbpOrientation::West => Orientation::Left,
+                };
+                let piece = Piece {
+                    tetromino,
+                    x: 0,
+                    y: 0,
+                    orientation: turned,
+                };
+                assert_eq!(shape(cells), shape(piece.get_coords()), "{}", what);
+
+                let path = moves_to(&game, tetromino, cells).expect(&what);
+                let mut after = game.clone();
+                for mov in path {
+                    after.move_piece(mov);
+                }
+                let mut coords = after.piece.get_coords();
+                coords.sort();
+                let mut cells = cells;
+                cells.sort();
+                assert_eq!(coords, cells, "{}", what);
+            }
+        }
+    }
+
+    #[test]
+    fn boards_go_from_the_bottom_up() {
+        let mut board = Board::new(10, 20);
+        let bottom = board.height() - 1;
+        board.grid[bottom][0] = Tetromino::G;
+        board.grid[bottom - 1][9] = Tetromino::T;
+        let tbp = tbp_board(&board.grid);
+
+        assert_eq!(tbp.len(), TBP_ROWS);
+        assert!(tbp.iter().all(|row| row.len() == 10));
+        assert_eq!(tbp[0][0], Some(Tetromino::G));
+        assert_eq!(tbp[0][1..], [None; 9]);
+        assert_eq!(tbp[1][9], Some(Tetromino::T));
+        assert_eq!(tbp[1][..9], [None; 9]);
+        // above the board is empty
+        assert!(tbp[2..].iter().flatten().all(Option::is_none));
+    }
+}
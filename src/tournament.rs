@@ -0,0 +1,513 @@
+// bots playing each other, for comparing them
+//
+// every pair of entrants plays on the same seeds, two games a seed:
+//   a score race, where each plays the seed on its own for up to `TOURNAMENT_PIECES` pieces
+//   and the higher score wins
+//   a versus game, lost by whoever tops out first (a draw if one of them gets through
+//   `TOURNAMENT_PIECES` pieces first)
+// games run on a made up clock, so they go as fast as the bots can think
+//
+// the results go into Elo ratings (a game at a time, in the order they were played) and
+// Glicko ratings (with the whole tournament as one rating period), and a report with 95%
+// intervals is written as CSV (a row per bot) and Markdown (the standings and each pair's
+// results against each other)
+// the ratings carry on from the last report written to the same place: a bot that's in the
+// CSV already starts on the ratings it finished the last tournament on, and the report is
+// then written over with the new ones (a bot that wasn't in it starts on `START_RATING`, and
+// one that's in it but didn't play this time is kept as it was)
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::args::Args;
+use crate::attack::AttackTable;
+use crate::constants::*;
+use crate::game::*;
+use crate::player::{self, Player};
+use crate::rating::{self, Glicko};
+use crate::session::Session;
+use crate::versus::Versus;
+
+// one game between two entrants, scored for the first
+struct GameResult {
+    sides: [usize; 2],
+    score: f64,
+}
+
+struct Standing {
+    name: String,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    race_scores: Vec<f64>,
+    elo: f64,
+    glicko: Glicko,
+}
+
+impl Standing {
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    fn win_rate(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+}
+
+// `progress` is told which seed is being played and, at the end, the report and where it
+// was written
+pub fn run(args: &Args, games: usize, progress: &mut dyn FnMut(&str)) -> io::Result<()> {
+    let names: Vec<String> = if args.entrants.is_empty() {
+        player::BUILT_IN
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    } else {
+        args.entrants.clone()
+    };
+    if names.len() < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a tournament needs at least two bots",
+        ));
+    }
+    // every bot is started once, so one that can't be is found out before anything is played
+    let entrants = names
+        .iter()
+        .map(|name| player::by_name(name))
+        .collect::<io::Result<Vec<_>>>()?;
+    let table = match &args.attack_table {
+        Some(path) => AttackTable::load(path)?,
+        None => AttackTable::default(),
+    };
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let seeds: Vec<u64> = (0..games).map(|_| rng.gen()).collect();
+    let (randomizer, width, height) = (args.randomizer, args.width, args.height);
+    let csv_path = args.report.with_extension("csv");
+    let previous = previous_ratings(&csv_path)?;
+
+    let mut standings: Vec<Standing> = names
+        .iter()
+        .map(|name| {
+            let (elo, glicko) = previous
+                .get(name)
+                .copied()
+                .unwrap_or((START_RATING, Glicko::default()));
+            Standing {
+                name: name.clone(),
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                race_scores: Vec::new(),
+                elo,
+                glicko,
+            }
+        })
+        .collect();
+    let mut results = Vec::new();
+
+    for (i, &seed) in seeds.iter().enumerate() {
+        progress(&format!("Seed {} of {}", i + 1, games));
+        // everyone's race on a seed is the same whoever it's against, so it's only played once
+        let mut scores = Vec::new();
+        for (standing, entrant) in standings.iter_mut().zip(&entrants) {
+            let score = race(entrant.as_ref(), seed, randomizer, width, height) as f64;
+            standing.race_scores.push(score);
+            scores.push(score);
+        }
+        for a in 0..names.len() {
+            for b in a + 1..names.len() {
+                let race_score = match scores[a].total_cmp(&scores[b]) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                };
+                let players: [&dyn Player; 2] = [entrants[a].as_ref(), entrants[b].as_ref()];
+                let versus_score = versus(players, seed, randomizer, width, height, &table);
+                for score in [race_score, versus_score] {
+                    results.push(GameResult {
+                        sides: [a, b],
+                        score,
+                    });
+                }
+            }
+        }
+    }
+
+    for result in &results {
+        let [a, b] = result.sides;
+        let [elo_a, elo_b] = rating::elo([standings[a].elo, standings[b].elo], result.score);
+        standings[a].elo = elo_a;
+        standings[b].elo = elo_b;
+        for (side, score) in [(a, result.score), (b, 1.0 - result.score)] {
+            let standing = &mut standings[side];
+            match score {
+                1.0 => standing.wins += 1,
+                0.0 => standing.losses += 1,
+                _ => standing.draws += 1,
+            }
+        }
+    }
+    // everyone's opponents are rated as they were going into the rating period
+    let starting: Vec<Glicko> = standings.iter().map(|standing| standing.glicko).collect();
+    let glickos: Vec<Glicko> = (0..standings.len())
+        .map(|side| {
+            let games: Vec<(Glicko, f64)> = results
+                .iter()
+                .filter_map(|result| match result.sides {
+                    [a, b] if a == side => Some((starting[b], result.score)),
+                    [a, b] if b == side => Some((starting[a], 1.0 - result.score)),
+                    _ => None,
+                })
+                .collect();
+            starting[side].update(&games)
+        })
+        .collect();
+    for (standing, glicko) in standings.iter_mut().zip(glickos) {
+        standing.glicko = glicko;
+    }
+
+    let head_to_head = head_to_head(&results, names.len());
+    let summary = format!(
+        "{} bots on {} seeds ({} games) on a {}x{} board, tournament seed {}",
+        names.len(),
+        games,
+        results.len(),
+        width,
+        height,
+        seed
+    );
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    order.sort_by(|&a, &b| {
+        standings[b]
+            .glicko
+            .rating
+            .total_cmp(&standings[a].glicko.rating)
+    });
+
+    let markdown_path = args.report.with_extension("md");
+    fs::write(&csv_path, csv(&standings, &order, &previous))?;
+    let markdown = markdown(&summary, &standings, &order, &head_to_head);
+    fs::write(&markdown_path, &markdown)?;
+    progress(&markdown);
+    // bots that crashed along the way say so
+    for (name, entrant) in names.iter().zip(&entrants) {
+        let status = entrant.status();
+        if !status.is_empty() {
+            progress(&format!("{}: {}", name, status.join(", ")));
+        }
+    }
+    progress(&format!(
+        "Report written to {} and {}",
+        csv_path.display(),
+        markdown_path.display()
+    ));
+    Ok(())
+}
+
+// plays a seed on its own and returns the score
+fn race(
+    player: &dyn Player,
+    seed: u64,
+    randomizer: Randomizer,
+    width: usize,
+    height: usize,
+) -> u32 {
+    player.new_game();
+    let mut time = 0.0;
+    let game = Game::with_board(seed, randomizer, Board::new(width, height));
+    let mut session = Session::new(game, Some("tournament games aren't recorded"), time);
+    session.start(time);
+    while !session.game_over && session.game.num_tetrominos < TOURNAMENT_PIECES {
+        time += FRAME_TIME;
+        session.update(time, &[], Some(player));
+    }
+    session.game.board.score
+}
+
+// plays a versus game and returns the score for the first side
+fn versus(
+    players: [&dyn Player; 2],
+    seed: u64,
+    randomizer: Randomizer,
+    width: usize,
+    height: usize,
+    table: &AttackTable,
+) -> f64 {
+    for player in players {
+        player.new_game();
+    }
+    let mut time = 0.0;
+    let mut versus = Versus::new(seed, randomizer, width, height, table.clone(), time);
+    versus.start(time);
+    let bots = players.map(Some);
+    while !versus.is_over()
+        && versus
+            .sessions
+            .iter()
+            .all(|session| session.game.num_tetrominos < TOURNAMENT_PIECES)
+    {
+        time += FRAME_TIME;
+        versus.update(time, [&[], &[]], bots);
+    }
+    match versus.winner() {
+        Some(0) => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+// each pair's points and games against each other, as [row][column]
+fn head_to_head(results: &[GameResult], entrants: usize) -> Vec<Vec<(f64, usize)>> {
+    let mut table = vec![vec![(0.0, 0); entrants]; entrants];
+    for result in results {
+        let [a, b] = result.sides;
+        table[a][b].0 += result.score;
+        table[a][b].1 += 1;
+        table[b][a].0 += 1.0 - result.score;
+        table[b][a].1 += 1;
+    }
+    table
+}
+
+// the Elo and Glicko ratings of each bot in the CSV report at `path`, if there is one
+fn previous_ratings(path: &Path) -> io::Result<HashMap<String, (f64, Glicko)>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
+    let invalid = |message: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), message),
+        )
+    };
+    let mut lines = text.lines();
+    let header = split_csv_line(lines.next().unwrap_or(""));
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| invalid(&format!("no `{}` column", name)))
+    };
+    let (bot, elo, glicko, deviation) = (
+        column("bot")?,
+        column("elo")?,
+        column("glicko")?,
+        column("glicko_deviation")?,
+    );
+
+    let mut ratings = HashMap::new();
+    for line in lines.filter(|line| !line.is_empty()) {
+        let fields = split_csv_line(line);
+        let number = |i: usize| -> io::Result<f64> {
+            fields
+                .get(i)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| invalid(&format!("bad row `{}`", line)))
+        };
+        let name = fields
+            .get(bot)
+            .ok_or_else(|| invalid(&format!("bad row `{}`", line)))?;
+        let glicko = Glicko {
+            rating: number(glicko)?,
+            deviation: number(deviation)?,
+        };
+        ratings.insert(name.clone(), (number(elo)?, glicko));
+    }
+    Ok(ratings)
+}
+
+// the fields of a CSV row, undoing `csv_field`'s quoting
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            (_, '"') => quoted = !quoted,
+            (false, ',') => fields.push(String::new()),
+            (_, c) => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+// quoted if it has to be, since bot commands can have anything in them
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// bots from the last report that didn't play this time are kept at the end with their
+// ratings, so they aren't lost
+fn csv(
+    standings: &[Standing],
+    order: &[usize],
+    previous: &HashMap<String, (f64, Glicko)>,
+) -> String {
+    let mut csv = String::from(
+        "bot,games,wins,draws,losses,win_rate,win_rate_low,win_rate_high,\
+         race_score,race_score_interval,elo,glicko,glicko_interval,glicko_deviation\n",
+    );
+    for &i in order {
+        let standing = &standings[i];
+        let (low, high) = rating::wilson(standing.win_rate(), standing.games());
+        let (score, interval) = rating::mean_interval(&standing.race_scores);
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{:.4},{:.4},{:.4},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1}",
+            csv_field(&standing.name),
+            standing.games(),
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.win_rate(),
+            low,
+            high,
+            score,
+            interval,
+            standing.elo,
+            standing.glicko.rating,
+            standing.glicko.interval(),
+            standing.glicko.deviation
+        );
+    }
+    let mut absent: Vec<_> = previous
+        .iter()
+        .filter(|(name, _)| standings.iter().all(|standing| &standing.name != *name))
+        .collect();
+    absent.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, (elo, glicko)) in absent {
+        let _ = writeln!(
+            csv,
+            "{},0,0,0,0,,,,,,{:.1},{:.1},{:.1},{:.1}",
+            csv_field(name),
+            elo,
+            glicko.rating,
+            glicko.interval(),
+            glicko.deviation
+        );
+    }
+    csv
+}
+
+fn markdown(
+    summary: &str,
+    standings: &[Standing],
+    order: &[usize],
+    head_to_head: &[Vec<(f64, usize)>],
+) -> String {
+    // pipes would split a bot's command across table cells
+    let name = |i: usize| format!("`{}`", standings[i].name.replace('|', "\\|"));
+    let mut markdown = format!("# Tournament\n\n{}\n\n## Standings\n\n", summary);
+    markdown.push_str(
+        "| Bot | Games | W | D | L | Win rate (95%) | Race score (95%) | Elo | Glicko (95%) |\n\
+         |---|---|---|---|---|---|---|---|---|\n",
+    );
+    for &i in order {
+        let standing = &standings[i];
+        let (low, high) = rating::wilson(standing.win_rate(), standing.games());
+        let (score, interval) = rating::mean_interval(&standing.race_scores);
+        let _ = writeln!(
+            markdown,
+            "| {} | {} | {} | {} | {} | {:.1}% ({:.1}-{:.1}%) | {:.0} ± {:.0} | {:.0} | {:.0} ± {:.0} |",
+            name(i),
+            standing.games(),
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.win_rate() * 100.0,
+            low * 100.0,
+            high * 100.0,
+            score,
+            interval,
+            standing.elo,
+            standing.glicko.rating,
+            standing.glicko.interval()
+        );
+    }
+
+    markdown.push_str("\n## Head to head\n\nPoints scored by each row's bot against each column's, out of the games they played\n\n|");
+    for &i in order {
+        let _ = write!(markdown, " | {}", name(i));
+    }
+    markdown.push_str(" |\n|---|");
+    markdown.push_str(&"---|".repeat(order.len()));
+    markdown.push('\n');
+    for &row in order {
+        let _ = write!(markdown, "| {}", name(row));
+        for &column in order {
+            let (points, games) = head_to_head[row][column];
+            if row == column {
+                markdown.push_str(" | -");
+            } else {
+                let _ = write!(markdown, " | {}/{}", points, games);
+            }
+        }
+        markdown.push_str(" |\n");
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(name: &str, elo: f64, rating: f64, deviation: f64) -> Standing {
+        Standing {
+            name: name.to_string(),
+            wins: 2,
+            draws: 1,
+            losses: 0,
+            race_scores: vec![100.0, 300.0],
+            elo,
+            glicko: Glicko { rating, deviation },
+        }
+    }
+
+    #[test]
+    fn ratings_carry_on_from_the_last_report() {
+        let standings = [
+            standing("heuristic", 1520.5, 1610.25, 120.5),
+            standing(r#"bot:python3 "my bots/a,b.py""#, 1479.5, 1390.0, 130.0),
+        ];
+        let previous = HashMap::from([("pc".to_string(), (1600.0, Glicko::default()))]);
+        let path =
+            std::env::temp_dir().join(format!("tetris-rs-{}-report.csv", std::process::id()));
+        fs::write(&path, csv(&standings, &[1, 0], &previous)).unwrap();
+        let ratings = previous_ratings(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ratings.len(), 3);
+        for standing in &standings {
+            let (elo, glicko) = ratings[&standing.name];
+            assert!((elo - standing.elo).abs() < 0.1, "{}", standing.name);
+            assert!((glicko.rating - standing.glicko.rating).abs() < 0.1);
+            assert!((glicko.deviation - standing.glicko.deviation).abs() < 0.1);
+        }
+        // one that didn't play is kept as it was
+        assert_eq!(ratings["pc"].0, 1600.0);
+        assert_eq!(ratings["pc"].1.deviation, START_DEVIATION);
+    }
+
+    #[test]
+    fn no_report_yet_is_no_ratings() {
+        let path = std::env::temp_dir().join("tetris-rs-no-such-report.csv");
+        assert!(previous_ratings(&path).unwrap().is_empty());
+    }
+}
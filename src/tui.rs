@@ -0,0 +1,362 @@
+// terminal frontend, for playing (or watching the bot) without a window
+// each cell is drawn as two coloured spaces so the board keeps its proportions
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{self, Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use crate::args::Args;
+use crate::constants::*;
+use crate::game::*;
+use crate::mode::Mode;
+use crate::opener::{self, Trainer};
+use crate::player::Player;
+use crate::session::Session;
+use crate::worker::BotWorker;
+
+// where the side panel starts, to the right of the board and its walls
+fn panel_column(board: &Board) -> u16 {
+    (board.width() * 2 + 4) as u16
+}
+
+pub fn run(args: &Args) -> io::Result<()> {
+    let clock = Instant::now();
+    let time = || clock.elapsed().as_secs_f64();
+
+    let mut session = match Session::from_args(args, time()) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return Ok(());
+        }
+    };
+    let player = match BotWorker::from_args(args) {
+        Ok(player) => player,
+        Err(err) => {
+            println!("Couldn't start the bot: {}", err);
+            return Ok(());
+        }
+    };
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(
+        out,
+        terminal::EnterAlternateScreen,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::Hide
+    )?;
+
+    let result = play(args, &mut session, &player, &mut out, time);
+
+    execute!(
+        out,
+        ResetColor,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()?;
+
+    // a finished game was already written out when it ended
+    if !session.game_over {
+        for message in session.finish(args) {
+            println!("{}", message);
+        }
+    }
+    println!("Final score: {}", session.game.board.score);
+    result
+}
+
+fn play(
+    args: &Args,
+    session: &mut Session,
+    player: &BotWorker,
+    out: &mut impl Write,
+    time: impl Fn() -> f64,
+) -> io::Result<()> {
+    // the bot is slowed down like in the window so it can be watched
+    let frame_time = Duration::from_millis(if args.manual { 16 } else { 100 });
+
+    // shown under the board, for things that would otherwise be printed
+    let mut status = String::new();
+    // shows where the opener being practised has each piece go, when playing by hand
+    let mut trainer = args
+        .opener
+        .as_ref()
+        .filter(|_| args.manual)
+        .map(|name| Trainer::new(opener::find(name)));
+
+    loop {
+        if session.is_out_of_pieces() {
+            return Ok(());
+        }
+
+        let was_over = session.game_over;
+        let mut moves = Vec::new();
+        let frame_end = Instant::now() + frame_time;
+        while let Some(remaining) = frame_end.checked_duration_since(Instant::now()) {
+            if !event::poll(remaining)? {
+                break;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Char('c') if ctrl => return Ok(()),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('s') => {
+                    status = match session.save(&args.save, time()) {
+                        Ok(()) => format!("Game saved to {}", args.save.display()),
+                        Err(err) => format!("Couldn't save game: {}", err),
+                    };
+                }
+                KeyCode::Char('r') if session.game_over => {
+                    session.restart(time());
+                    status.clear();
+                }
+                KeyCode::Char('g') if args.manual => session.add_garbage(time()),
+                KeyCode::Char('z') if args.manual => session.undo(),
+                KeyCode::Char('y') if args.manual => session.redo(),
+                KeyCode::Char(digit @ '1'..='3') if args.manual => {
+                    session.cycle_next_piece(digit as usize - '1' as usize)
+                }
+                // with no way of telling a key's being held, shift moves all the way over
+                KeyCode::Left | KeyCode::Right
+                    if args.manual && key.modifiers.contains(KeyModifiers::SHIFT) =>
+                {
+                    let mov = if key.code == KeyCode::Left {
+                        Move::Left
+                    } else {
+                        Move::Right
+                    };
+                    session.finesse.input(mov);
+                    session.shift_to_wall(time(), mov);
+                }
+                code if args.manual => {
+                    let mov = match code {
+                        KeyCode::Up => Move::Rotate,
+                        KeyCode::Down => Move::Down,
+                        KeyCode::Left => Move::Left,
+                        KeyCode::Right => Move::Right,
+                        KeyCode::Char(' ') => Move::Drop,
+                        KeyCode::Char('c') => Move::Hold,
+                        _ => continue,
+                    };
+                    moves.push(mov);
+                }
+                _ => (),
+            }
+        }
+
+        let bot = (!args.manual).then_some(player as &dyn Player);
+        session.update(time(), &moves, bot);
+        if session.game_over && !was_over {
+            let mut messages = session.finish(args);
+            messages.push(format!("{}  r restart  q quit", session.title()));
+            status = messages.join("  ");
+        }
+
+        // the bot's or the trainer's, under the session's
+        let extra_stats: Vec<String> = if args.manual {
+            let mut stats = session.finesse.stats();
+            stats.extend(trainer.as_ref().map(Trainer::status));
+            stats
+        } else {
+            player.stats()
+        };
+        let hint = trainer.as_mut().and_then(|t| t.hint(&session.game));
+        draw(
+            out,
+            session,
+            args.manual,
+            &extra_stats,
+            hint.as_ref(),
+            &status,
+            time(),
+        )?;
+        out.flush()?;
+    }
+}
+
+fn color(tetromino: Tetromino) -> Color {
+    let color = tetromino.get_color();
+    Color::Rgb {
+        r: (color.r * 255.0) as u8,
+        g: (color.g * 255.0) as u8,
+        b: (color.b * 255.0) as u8,
+    }
+}
+
+fn draw_cell(out: &mut impl Write, tetromino: Tetromino) -> io::Result<()> {
+    match tetromino {
+        Tetromino::E => queue!(
+            out,
+            SetForegroundColor(Color::DarkGrey),
+            Print(" ."),
+            ResetColor
+        ),
+        _ => queue!(
+            out,
+            SetBackgroundColor(color(tetromino)),
+            Print("  "),
+            ResetColor
+        ),
+    }
+}
+
+fn draw(
+    out: &mut impl Write,
+    session: &Session,
+    manual: bool,
+    extra_stats: &[String],
+    hint: Option<&Piece>,
+    status: &str,
+    time: f64,
+) -> io::Result<()> {
+    let game = &session.game;
+    let height = game.board.visible_height() as u16;
+    let panel = panel_column(&game.board);
+
+    // the board and its walls
+    let wall = format!("+{}+", "-".repeat(game.board.width() * 2));
+    queue!(out, cursor::MoveTo(0, 0), Print(&wall))?;
+    let hinted = hint.map_or([(-1, -1); 4], Piece::get_coords);
+    for (y, row) in game.board.visible_rows().iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, y as u16 + 1), Print("|"))?;
+        for (x, &cell) in row.iter().enumerate() {
+            let at = (x as isize, (y + HIDDEN_ROWS) as isize);
+            match hint {
+                // where the opener has the piece go, outlined in its colour
+                Some(hint) if cell == Tetromino::E && hinted.contains(&at) => queue!(
+                    out,
+                    SetForegroundColor(color(hint.tetromino)),
+                    Print("[]"),
+                    ResetColor
+                )?,
+                _ => draw_cell(out, cell)?,
+            }
+        }
+        queue!(out, Print("|"))?;
+    }
+    queue!(out, cursor::MoveTo(0, height + 1), Print(&wall))?;
+
+    // the side panel, cleared first since pieces in it change shape
+    for y in 1..=height {
+        queue!(
+            out,
+            cursor::MoveTo(panel, y),
+            terminal::Clear(terminal::ClearType::UntilNewLine)
+        )?;
+    }
+    queue!(
+        out,
+        cursor::MoveTo(panel, 1),
+        SetForegroundColor(Color::Yellow),
+        Print("Score"),
+        ResetColor,
+        cursor::MoveTo(panel, 2),
+        Print(game.board.score)
+    )?;
+
+    queue!(
+        out,
+        cursor::MoveTo(panel, 4),
+        SetForegroundColor(Color::Yellow),
+        Print("Next"),
+        ResetColor
+    )?;
+    let mut row = 5;
+    for &next_piece in game.piece_chooser.next_pieces.iter().rev() {
+        draw_mini_piece(out, next_piece, panel, row)?;
+        row += 3;
+    }
+
+    queue!(
+        out,
+        cursor::MoveTo(panel, row + 1),
+        SetForegroundColor(Color::Yellow),
+        Print("Hold"),
+        ResetColor
+    )?;
+    if let Some(held) = game.held {
+        draw_mini_piece(out, held, panel, row + 2)?;
+    }
+
+    let stats = session
+        .stats(time)
+        .into_iter()
+        .chain(extra_stats.iter().cloned());
+    for (i, stat) in stats.enumerate() {
+        queue!(out, cursor::MoveTo(panel, row + 5 + i as u16), Print(stat))?;
+    }
+
+    let controls = if manual && session.mode == Mode::Zen {
+        "arrows move  shift+arrows to wall  space drop  c hold  z undo  y redo  1-3 change next  s save  q quit"
+    } else if manual && session.mode == Mode::Endless {
+        "arrows move  shift+arrows to wall  space drop  c hold  g garbage  s save  q quit"
+    } else if manual {
+        "arrows move  shift+arrows to wall  space drop  c hold  s save  q quit"
+    } else {
+        "s save  q quit"
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, height + 2),
+        terminal::Clear(terminal::ClearType::UntilNewLine),
+        style::SetAttribute(style::Attribute::Dim),
+        Print(controls),
+        style::SetAttribute(style::Attribute::Reset),
+        cursor::MoveTo(0, height + 3),
+        terminal::Clear(terminal::ClearType::UntilNewLine),
+        Print(status)
+    )?;
+
+    // the breakdown goes under the status once the game is over
+    let results = if session.game_over {
+        session.results()
+    } else {
+        Vec::new()
+    };
+    for (i, line) in results.iter().enumerate() {
+        queue!(
+            out,
+            cursor::MoveTo(0, height + 4 + i as u16),
+            terminal::Clear(terminal::ClearType::UntilNewLine),
+            Print(line)
+        )?;
+    }
+    queue!(
+        out,
+        cursor::MoveTo(0, height + 4 + results.len() as u16),
+        terminal::Clear(terminal::ClearType::FromCursorDown)
+    )
+}
+
+fn draw_mini_piece(
+    out: &mut impl Write,
+    tetromino: Tetromino,
+    column: u16,
+    row: u16,
+) -> io::Result<()> {
+    for (x, y) in Piece::new(tetromino).get_coords() {
+        queue!(
+            out,
+            cursor::MoveTo(column + x as u16 * 2, row + y as u16),
+            SetBackgroundColor(color(tetromino)),
+            Print("  "),
+            ResetColor
+        )?;
+    }
+    Ok(())
+}
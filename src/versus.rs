@@ -0,0 +1,176 @@
+// two games side by side, where clearing lines sends garbage over to the other one
+//
+// garbage that's sent waits in the opponent's queue for `GARBAGE_DELAY` seconds, and
+// clearing lines cancels what's waiting before anything gets sent back
+// once it's ready it comes up the next time the opponent places a piece without clearing
+
+use std::collections::VecDeque;
+
+use crate::attack::{AttackTable, Attacker};
+use crate::constants::*;
+use crate::game::*;
+use crate::mode::Mode;
+use crate::player::Player;
+use crate::session::Session;
+
+// garbage on its way to a board, oldest first
+#[derive(Default)]
+pub struct GarbageQueue {
+    // rows in each attack and when they can come up
+    pub attacks: VecDeque<(usize, f64)>,
+}
+
+impl GarbageQueue {
+    pub fn push(&mut self, rows: usize, ready_time: f64) {
+        self.attacks.push_back((rows, ready_time));
+    }
+
+    // cancels up to `rows` of the waiting garbage, returns the rows left over
+    pub fn cancel(&mut self, rows: usize) -> usize {
+        let mut rows = rows;
+        while rows > 0 {
+            let Some((queued, _)) = self.attacks.front_mut() else {
+                break;
+            };
+            let cancelled = rows.min(*queued);
+            *queued -= cancelled;
+            rows -= cancelled;
+            if *queued == 0 {
+                self.attacks.pop_front();
+            }
+        }
+        rows
+    }
+
+    // takes out the attacks that are ready to come up
+    pub fn take_ready(&mut self, time: f64) -> Vec<usize> {
+        let mut ready = Vec::new();
+        while let Some(&(rows, ready_time)) = self.attacks.front() {
+            if ready_time > time {
+                break;
+            }
+            ready.push(rows);
+            self.attacks.pop_front();
+        }
+        ready
+    }
+
+    // rows waiting, and how many of those are ready to come up
+    pub fn rows(&self, time: f64) -> (usize, usize) {
+        let waiting = self.attacks.iter().map(|&(rows, _)| rows).sum();
+        let ready = self
+            .attacks
+            .iter()
+            .filter(|&&(_, ready_time)| ready_time <= time)
+            .map(|&(rows, _)| rows)
+            .sum();
+        (waiting, ready)
+    }
+}
+
+pub struct Versus {
+    pub sessions: [Session; 2],
+    pub attackers: [Attacker; 2],
+    // garbage waiting to come up under each board
+    pub queues: [GarbageQueue; 2],
+    pub table: AttackTable,
+}
+
+impl Versus {
+    // both sides get the same pieces
+    pub fn new(
+        seed: u64,
+        randomizer: Randomizer,
+        width: usize,
+        height: usize,
+        table: AttackTable,
+        time: f64,
+    ) -> Versus {
+        let session = || {
+            let game = Game::with_board(seed, randomizer, Board::new(width, height));
+            let mut session = Session::new(game, Some("versus games aren't recorded"), time);
+            session.mode = Mode::Versus;
+            session
+        };
+        Versus {
+            sessions: [session(), session()],
+            attackers: Default::default(),
+            queues: Default::default(),
+            table,
+        }
+    }
+
+    // starts a new match with fresh pieces
+    pub fn restart(&mut self, time: f64) {
+        let game = &self.sessions[0].game;
+        let (width, height) = (game.board.width(), game.board.visible_height());
+        let randomizer = game.piece_chooser.randomizer();
+        *self = Versus::new(
+            rand::random(),
+            randomizer,
+            width,
+            height,
+            self.table.clone(),
+            time,
+        );
+    }
+
+    pub fn start(&mut self, time: f64) {
+        for session in &mut self.sessions {
+            session.start(time);
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.sessions.iter().any(|session| session.game_over)
+    }
+
+    // the side still standing, or None for a draw (or while it's still going)
+    pub fn winner(&self) -> Option<usize> {
+        self.sessions.iter().position(|session| session.finished)
+    }
+
+    // runs one frame of both games, with the moves and bot (if any) for each side
+    pub fn update(&mut self, time: f64, moves: [&[Move]; 2], players: [Option<&dyn Player>; 2]) {
+        if self.is_over() {
+            return;
+        }
+        for side in 0..2 {
+            let Some(lock) = self.sessions[side].update(time, moves[side], players[side]) else {
+                continue;
+            };
+            let attack = self.attackers[side].attack(&self.table, lock);
+            let sent = self.queues[side].cancel(attack);
+            if sent > 0 {
+                self.queues[1 - side].push(sent, time + GARBAGE_DELAY);
+            }
+            if lock.lines == 0 {
+                for rows in self.queues[side].take_ready(time) {
+                    self.sessions[side].receive_garbage(time, rows);
+                }
+            }
+        }
+
+        // whoever is left standing wins, both topping out on the same frame is a draw
+        let over = [self.sessions[0].game_over, self.sessions[1].game_over];
+        for side in 0..2 {
+            if over[1 - side] && !over[side] {
+                self.sessions[side].end(time, true);
+            }
+        }
+    }
+
+    // what's shown under a side's other stats
+    pub fn stats(&self, side: usize, time: f64) -> Vec<String> {
+        let attacker = &self.attackers[side];
+        let mut stats = self.sessions[side].stats(time);
+        stats.push(format!("Sent {}", attacker.sent));
+        if attacker.combo > 1 {
+            stats.push(format!("Combo {}", attacker.combo - 1));
+        }
+        if attacker.back_to_back {
+            stats.push("B2B".to_string());
+        }
+        stats
+    }
+}
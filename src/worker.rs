@@ -0,0 +1,180 @@
+// runs a bot on a thread of its own, so a slow one doesn't hold up drawing
+//
+// a `BotWorker` is handed to `Session::update` like any other player, but rather than
+// thinking there and then it passes the game over to the bot's thread and has no moves to
+// give (so the game carries on, gravity and all) until the bot answers
+// each piece gets a budget of thinking time, and once that's up the piece is hard dropped
+// and the request is cancelled: whatever the bot comes back with is thrown away, and if it
+// hadn't started on it yet it never does
+// the same goes for a request for a game that's since changed (restarted, undone...)
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crate::args::Args;
+use crate::game::*;
+use crate::player::{self, Player, Thoughts};
+
+// what the bot was last asked about
+struct Request {
+    id: usize,
+    // the game it was for, which is told apart by the pieces placed and the piece and hold
+    game: (usize, Tetromino, Option<Tetromino>),
+    sent: Instant,
+}
+
+// what comes back from the bot's thread for a request
+struct Answer {
+    id: usize,
+    moves: Vec<Move>,
+    // seconds it took
+    took: f64,
+    thoughts: Option<Thoughts>,
+    status: Vec<String>,
+}
+
+// how long the bot has been taking
+#[derive(Default)]
+pub struct ThinkingTime {
+    // seconds spent thinking about each piece, as measured on the bot's thread
+    pub total: f64,
+    pub longest: f64,
+    pub last: f64,
+    pub answers: usize,
+    // pieces it ran out of time on
+    pub timeouts: usize,
+}
+
+pub struct BotWorker {
+    requests: Sender<(usize, Game)>,
+    answers: Receiver<Answer>,
+    // the id of the newest request, anything older has been cancelled
+    current: Arc<AtomicUsize>,
+    next_id: Cell<usize>,
+    request: RefCell<Option<Request>>,
+    // seconds the bot gets for each piece
+    pub budget: f64,
+    pub thinking: RefCell<ThinkingTime>,
+    // what the bot said about the last piece it answered for
+    thoughts: RefCell<Option<Thoughts>>,
+    // what the bot said about how it's doing with its last answer
+    status: RefCell<Vec<String>>,
+}
+
+impl BotWorker {
+    pub fn new(player: Box<dyn Player + Send>, budget: f64) -> BotWorker {
+        let (requests, incoming) = mpsc::channel::<(usize, Game)>();
+        let (sender, answers) = mpsc::channel();
+        let current = Arc::new(AtomicUsize::new(0));
+        let newest = Arc::clone(&current);
+        thread::spawn(move || {
+            for (id, game) in incoming {
+                if id < newest.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let start = Instant::now();
+                let moves = player.choose_moves(&game);
+                let answer = Answer {
+                    id,
+                    moves,
+                    took: start.elapsed().as_secs_f64(),
+                    thoughts: player.thoughts(),
+                    status: player.status(),
+                };
+                if sender.send(answer).is_err() {
+                    return;
+                }
+            }
+        });
+        BotWorker {
+            requests,
+            answers,
+            current,
+            next_id: Cell::new(0),
+            request: RefCell::new(None),
+            budget,
+            thinking: RefCell::new(ThinkingTime::default()),
+            thoughts: RefCell::new(None),
+            status: RefCell::new(Vec::new()),
+        }
+    }
+
+    // the bot asked for on the command line, with the think time asked for
+    pub fn from_args(args: &Args) -> io::Result<BotWorker> {
+        Ok(BotWorker::new(player::from_args(args)?, args.think_time))
+    }
+
+    // drops whatever the bot is working on
+    fn cancel(&self) {
+        self.current.store(self.next_id.get(), Ordering::SeqCst);
+        *self.request.borrow_mut() = None;
+    }
+
+    // what's shown under the other stats
+    pub fn stats(&self) -> Vec<String> {
+        let thinking = self.thinking.borrow();
+        let mut stats = Vec::new();
+        if thinking.answers > 0 {
+            let average = thinking.total / thinking.answers as f64;
+            stats.push(format!("Think {:.0}ms", thinking.last * 1000.0));
+            stats.push(format!("Avg {:.0}ms", average * 1000.0));
+            stats.push(format!("Max {:.0}ms", thinking.longest * 1000.0));
+        }
+        if thinking.timeouts > 0 {
+            stats.push(format!("Timeouts {}", thinking.timeouts));
+        }
+        stats.extend(self.status.borrow().iter().cloned());
+        stats
+    }
+}
+
+impl Player for BotWorker {
+    fn choose_moves(&self, game: &Game) -> Vec<Move> {
+        let key = (game.num_tetrominos, game.piece.tetromino, game.held);
+        let request = self.request.borrow_mut().take();
+        let Some(request) = request.filter(|request| request.game == key) else {
+            self.cancel();
+            let id = self.next_id.get();
+            self.next_id.set(id + 1);
+            self.current.store(id, Ordering::SeqCst);
+            // a bot thread that's gone (it panicked) is left to time out
+            let _ = self.requests.send((id, game.clone()));
+            *self.request.borrow_mut() = Some(Request {
+                id,
+                game: key,
+                sent: Instant::now(),
+            });
+            return Vec::new();
+        };
+
+        // answers to cancelled requests can still be on their way
+        while let Ok(answer) = self.answers.try_recv() {
+            *self.status.borrow_mut() = answer.status;
+            if answer.id == request.id {
+                let mut thinking = self.thinking.borrow_mut();
+                thinking.total += answer.took;
+                thinking.longest = thinking.longest.max(answer.took);
+                thinking.last = answer.took;
+                thinking.answers += 1;
+                *self.thoughts.borrow_mut() = answer.thoughts;
+                return answer.moves;
+            }
+        }
+        if request.sent.elapsed().as_secs_f64() >= self.budget {
+            self.thinking.borrow_mut().timeouts += 1;
+            self.cancel();
+            return vec![Move::Drop];
+        }
+        *self.request.borrow_mut() = Some(request);
+        Vec::new()
+    }
+
+    fn thoughts(&self) -> Option<Thoughts> {
+        self.thoughts.borrow().clone()
+    }
+}